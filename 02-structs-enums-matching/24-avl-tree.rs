@@ -0,0 +1,459 @@
+// avl.rs
+
+/* The 'Node<T>' tree from struct6.rs has a problem. If the data arrives in sorted order, every new node goes to the right of the last one, and the 'tree' is really a linked list; finding anything means walking the whole thing. Real ordered collections like 'BTreeMap' keep themselves balanced. Here we do the same with an AVL tree, one of the oldest self-balancing trees, using the same 'Option<Box<Node<T>>>' representation.
+
+The rule of an AVL tree is that the heights of the left and right subtrees of every node differ by at most one. Then the height of the whole tree is never much more than log2 of the number of nodes. Each node remembers its height, so we can check the rule without walking the subtrees. */
+
+use std::borrow::Borrow;
+use std::cmp::{self, Ordering};
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::{Bound, RangeBounds};
+
+type NodeBox<T> = Option<Box<Node<T>>>;
+
+#[derive(Debug)]
+struct Node<T> {
+    payload: T,
+    height: usize,
+    left: NodeBox<T>,
+    right: NodeBox<T>
+}
+
+fn height<T>(node: &NodeBox<T>) -> usize {
+    node.as_ref().map_or(0, |n| n.height)
+}
+
+/* Balancing needs a total order, so 'T' must implement 'Ord' rather than 'PartialOrd'; 'f64' can't go into this tree because 'NaN' isn't less than, equal to or greater than anything.
+
+When one side gets too tall, we fix it with a rotation. Rotating right makes the left child the new top of this subtree. The old top becomes its right child, and the left child's right subtree moves across to become the old top's left subtree. The order of everything is unchanged, but one level has moved from the left side to the right.
+
+Rotations move boxes around, and this is much easier if we own them. So these methods take 'self: Box<Self>' and return the new top of the subtree, rather than working through '&mut self'. */
+
+impl <T: Ord> Node<T> {
+    fn new(s: T) -> Node<T> {
+        Node{payload: s, height: 1, left: None, right: None}
+    }
+
+    fn update(&mut self) {
+        self.height = 1 + cmp::max(height(&self.left), height(&self.right));
+    }
+
+    // positive if the left side is taller
+    fn balance(&self) -> isize {
+        height(&self.left) as isize - height(&self.right) as isize
+    }
+
+    fn rotate_right(mut self: Box<Self>) -> Box<Self> {
+        let mut left = self.left.take().expect("rotate_right needs a left child");
+        self.left = left.right.take();
+        self.update();
+        left.right = Some(self);
+        left.update();
+        left
+    }
+
+    fn rotate_left(mut self: Box<Self>) -> Box<Self> {
+        let mut right = self.right.take().expect("rotate_left needs a right child");
+        self.right = right.left.take();
+        self.update();
+        right.left = Some(self);
+        right.update();
+        right
+    }
+
+    fn rebalance(mut self: Box<Self>) -> Box<Self> {
+        self.update();
+        let balance = self.balance();
+        if balance > 1 {
+            if self.left.as_ref().unwrap().balance() < 0 {
+                self.left = self.left.take().map(|n| n.rotate_left());
+            }
+            self.rotate_right()
+        } else if balance < -1 {
+            if self.right.as_ref().unwrap().balance() > 0 {
+                self.right = self.right.take().map(|n| n.rotate_right());
+            }
+            self.rotate_left()
+        } else {
+            self
+        }
+    }
+}
+
+/* If a node is left-heavy but its left child leans the other way, a single rotation would just make it right-heavy. So first the child is rotated the other way; this is the famous 'double rotation'.
+
+Insertion works like the 'insert' method in struct6.rs, except that it takes the subtree by value and gives back the rebalanced subtree. Every node on the way down is rebalanced on the way back up. If an equal value is already in the tree, it's replaced and the old value is handed back through 'old'; this is what 'BTreeMap::insert' does with values. */
+
+fn insert<T: Ord>(node: NodeBox<T>, data: T, old: &mut Option<T>) -> Box<Node<T>> {
+    let mut n = match node {
+        Some(n) => n,
+        None => return Box::new(Node::new(data))
+    };
+    match data.cmp(&n.payload) {
+        Ordering::Less => n.left = Some(insert(n.left.take(), data, old)),
+        Ordering::Greater => n.right = Some(insert(n.right.take(), data, old)),
+        Ordering::Equal => {
+            *old = Some(mem::replace(&mut n.payload, data));
+            return n;
+        }
+    }
+    n.rebalance()
+}
+
+/* Removing a node with at most one child is easy, since the child just takes its place. Otherwise the node's value is replaced by the next value in order, which is the leftmost value in its right subtree. ('or'-patterns with bindings like '(None, child) | (child, None)' save us writing out two identical cases.)
+
+The key type 'Q' is there so that we don't need a whole 'T' to look something up. This is the same trick that lets you look up a 'HashMap<String,_>' with a '&str'; 'T: Borrow<Q>' means that a '&Q' can be got from a '&T', and they compare in the same way. The '?' on an 'Option' returns 'None' early, which is the right answer for an empty subtree. */
+
+fn remove<T, Q>(node: NodeBox<T>, key: &Q, removed: &mut Option<T>) -> NodeBox<T>
+where T: Ord + Borrow<Q>, Q: Ord + ?Sized {
+    let mut n = node?;
+    match key.cmp(n.payload.borrow()) {
+        Ordering::Less => n.left = remove(n.left.take(), key, removed),
+        Ordering::Greater => n.right = remove(n.right.take(), key, removed),
+        Ordering::Equal => match (n.left.take(), n.right.take()) {
+            (None, child) | (child, None) => {
+                *removed = Some(n.payload);
+                return child;
+            },
+            (Some(left), Some(right)) => {
+                let (right, next) = remove_first(right);
+                n.left = Some(left);
+                n.right = right;
+                *removed = Some(mem::replace(&mut n.payload, next));
+            }
+        }
+    }
+    Some(n.rebalance())
+}
+
+fn remove_first<T: Ord>(mut node: Box<Node<T>>) -> (NodeBox<T>, T) {
+    match node.left.take() {
+        None => {
+            let node = *node;
+            (node.right, node.payload)
+        },
+        Some(left) => {
+            let (left, first) = remove_first(left);
+            node.left = left;
+            (Some(node.rebalance()), first)
+        }
+    }
+}
+
+/* The tree itself keeps the root and a count, so 'len' doesn't have to walk the tree.
+
+The 'visit' method in struct5.rs could only print. Proper iterators are better, since then all of the iterator methods are available. An iterator can't use recursion, so it keeps its own stack of nodes still to be visited. The three traversal orders only differ in when a node's own value comes out compared with its children, so one iterator can do all three. The flag says whether a node has been 'expanded' into its children yet; when an expanded node comes off the stack, its value is next. Pushing happens in reverse order, since the last thing pushed is the first one popped. */
+
+#[derive(Debug)]
+struct Tree<T> {
+    root: NodeBox<T>,
+    len: usize
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Order {
+    Pre, In, Post
+}
+
+struct Iter<'a,T> {
+    stack: Vec<(&'a Node<T>, bool)>,
+    order: Order
+}
+
+impl <'a,T> Iter<'a,T> {
+    fn new(root: &'a NodeBox<T>, order: Order) -> Iter<'a,T> {
+        let mut iter = Iter{stack: Vec::new(), order: order};
+        iter.push(root);
+        iter
+    }
+
+    fn push(&mut self, node: &'a NodeBox<T>) {
+        if let Some(ref n) = *node {
+            self.stack.push((n, false));
+        }
+    }
+}
+
+impl <'a,T> Iterator for Iter<'a,T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        while let Some((node, ready)) = self.stack.pop() {
+            if ready {
+                return Some(&node.payload);
+            }
+            match self.order {
+                Order::Pre => {
+                    self.push(&node.right);
+                    self.push(&node.left);
+                    self.stack.push((node, true));
+                },
+                Order::In => {
+                    self.push(&node.right);
+                    self.stack.push((node, true));
+                    self.push(&node.left);
+                },
+                Order::Post => {
+                    self.stack.push((node, true));
+                    self.push(&node.right);
+                    self.push(&node.left);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl <T: Ord> Tree<T> {
+    fn new() -> Tree<T> {
+        Tree{root: None, len: 0}
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn height(&self) -> usize {
+        height(&self.root)
+    }
+
+    fn insert(&mut self, data: T) -> Option<T> {
+        let mut old = None;
+        self.root = Some(insert(self.root.take(), data, &mut old));
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    fn remove<Q>(&mut self, key: &Q) -> Option<T>
+    where T: Borrow<Q>, Q: Ord + ?Sized {
+        let mut removed = None;
+        self.root = remove(self.root.take(), key, &mut removed);
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    fn get<Q>(&self, key: &Q) -> Option<&T>
+    where T: Borrow<Q>, Q: Ord + ?Sized {
+        let mut node = &self.root;
+        while let Some(ref n) = *node {
+            match key.cmp(n.payload.borrow()) {
+                Ordering::Less => node = &n.left,
+                Ordering::Greater => node = &n.right,
+                Ordering::Equal => return Some(&n.payload)
+            }
+        }
+        None
+    }
+
+    fn contains<Q>(&self, key: &Q) -> bool
+    where T: Borrow<Q>, Q: Ord + ?Sized {
+        self.get(key).is_some()
+    }
+
+    fn iter(&self) -> Iter<'_,T> {
+        Iter::new(&self.root, Order::In)
+    }
+
+    fn pre_order(&self) -> Iter<'_,T> {
+        Iter::new(&self.root, Order::Pre)
+    }
+
+    fn post_order(&self) -> Iter<'_,T> {
+        Iter::new(&self.root, Order::Post)
+    }
+
+    fn range<Q,R>(&self, range: R) -> impl Iterator<Item = &T>
+    where T: Borrow<Q>, Q: Ord + ?Sized, R: RangeBounds<Q> {
+        let mut iter = Iter{stack: Vec::new(), order: Order::In};
+        let mut node = &self.root;
+        while let Some(ref n) = *node {
+            let after_start = match range.start_bound() {
+                Bound::Included(start) => n.payload.borrow() >= start,
+                Bound::Excluded(start) => n.payload.borrow() > start,
+                Bound::Unbounded => true
+            };
+            if after_start {
+                iter.push(&n.right);
+                iter.stack.push((n, true));
+                node = &n.left;
+            } else {
+                node = &n.right;
+            }
+        }
+        iter.take_while(move |v| match range.end_bound() {
+            Bound::Included(end) => (*v).borrow() <= end,
+            Bound::Excluded(end) => (*v).borrow() < end,
+            Bound::Unbounded => true
+        })
+    }
+}
+
+/* 'range' doesn't visit the whole tree to find the start. It walks down from the root, and every node at or after the start goes on the stack exactly as the in-order iterator would have left it, with its right subtree underneath. Nodes before the start are skipped together with their left subtrees. Then we just iterate until we pass the end of the range. 'RangeBounds' is implemented by all the range types, so 'tree.range(3..=5)' and 'tree.range(..3)' both work.
+
+Implementing 'IntoIterator' for '&Tree<T>' makes 'for x in &tree' work, and 'FromIterator' makes 'collect' work. It's easiest to write 'FromIterator' in terms of 'Extend'. */
+
+impl <'a,T: Ord> IntoIterator for &'a Tree<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a,T>;
+
+    fn into_iter(self) -> Iter<'a,T> {
+        self.iter()
+    }
+}
+
+impl <T: Ord> Extend<T> for Tree<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for x in iter {
+            self.insert(x);
+        }
+    }
+}
+
+impl <T: Ord> FromIterator<T> for Tree<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Tree<T> {
+        let mut tree = Tree::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+// Inserting sorted data now gives a tree of height 10 rather than 1000
+let tree: Tree<i32> = (1..=1000).collect();
+println!("len {} height {}", tree.len(), tree.height());
+// len 1000 height 10
+
+let mut tree: Tree<&str> = ["root", "one", "two", "four"].iter().cloned().collect();
+println!("{:?}", tree.iter().collect::<Vec<_>>());
+println!("{:?}", tree.pre_order().collect::<Vec<_>>());
+println!("{:?}", tree.post_order().collect::<Vec<_>>());
+println!("{} {}", tree.contains("two"), tree.contains("three"));
+// ["four", "one", "root", "two"]
+// ["root", "one", "four", "two"]
+// ["four", "one", "two", "root"]
+// true false
+
+tree.extend(vec!["three", "five"]);
+println!("{:?}", tree.range("o".."tx").collect::<Vec<_>>());
+println!("{:?}", tree.remove("root"));
+println!("{:?} {}", tree.iter().collect::<Vec<_>>(), tree.len());
+// ["one", "root", "three", "two"]
+// Some("root")
+// ["five", "four", "one", "three", "two"] 5
+
+// The pre-order shows the shape; 4 is at the top, with 2 and 6 below it
+let mut tree: Tree<u32> = (1..=7).collect();
+println!("{:?}", tree.pre_order().collect::<Vec<_>>());
+println!("{:?}", tree.range(3..=5).collect::<Vec<_>>());
+println!("{:?}", tree.range(..3).collect::<Vec<_>>());
+tree.remove(&4);
+println!("{:?}", tree.pre_order().collect::<Vec<_>>());
+// [4, 2, 1, 3, 6, 5, 7]
+// [3, 4, 5]
+// [1, 2]
+// [5, 2, 1, 3, 6, 7]
+
+/* This is an ordered set, but it's easy to make it into an ordered map. A set of key-value pairs which compare by key only is a map. With 'Borrow<K>' we can look up a pair using just the key, and since 'insert' replaces an equal value, inserting a pair with an existing key replaces its value. */
+
+#[derive(Debug, Clone)]
+struct Pair<K,V> {
+    key: K,
+    value: V
+}
+
+impl <K: Ord,V> PartialEq for Pair<K,V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl <K: Ord,V> Eq for Pair<K,V> {}
+
+impl <K: Ord,V> PartialOrd for Pair<K,V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl <K: Ord,V> Ord for Pair<K,V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl <K,V> Borrow<K> for Pair<K,V> {
+    fn borrow(&self) -> &K {
+        &self.key
+    }
+}
+
+let mut ages = Tree::new();
+ages.insert(Pair{key: "bob", value: 42});
+ages.insert(Pair{key: "alice", value: 37});
+println!("{:?}", ages.insert(Pair{key: "bob", value: 43}));
+println!("{:?}", ages.get(&"bob").map(|p| p.value));
+// Some(Pair { key: "bob", value: 42 })
+// Some(43)
+
+/* How do we know that all this is correct? Tree code is notorious for bugs that only show up for particular shapes of tree. A good way to test a data structure is to do lots of random operations on it and on a trusted implementation of the same thing, here 'BTreeMap', and check that they always agree. We also check that the tree really is balanced and that every stored height is right.
+
+There's no 'rand' in the standard library, but a tiny 'xorshift' generator is quite good enough for making test data. Because it always starts from the same seed, a failure can be reproduced exactly. */
+
+fn check<T: Ord>(node: &NodeBox<T>) -> usize {
+    match *node {
+        None => 0,
+        Some(ref n) => {
+            let (l, r) = (check(&n.left), check(&n.right));
+            assert!((l as isize - r as isize).abs() <= 1, "unbalanced");
+            assert_eq!(n.height, 1 + cmp::max(l, r), "wrong height");
+            n.height
+        }
+    }
+}
+
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+use std::collections::BTreeMap;
+let mut rng = Rng(0x2545F4914F6CDD1D);
+for _ in 0..200 {
+    let mut tree = Tree::new();
+    let mut model = BTreeMap::new();
+    for _ in 0..500 {
+        let key = rng.below(100);
+        if rng.below(3) == 0 {
+            assert_eq!(tree.remove(&key).map(|p: Pair<u64,u64>| p.value), model.remove(&key));
+        } else {
+            let value = rng.next();
+            assert_eq!(tree.insert(Pair{key: key, value: value}).map(|p| p.value), model.insert(key, value));
+        }
+        assert_eq!(tree.len(), model.len());
+        let key = rng.below(100);
+        assert_eq!(tree.get(&key).map(|p| p.value), model.get(&key).cloned());
+    }
+    check(&tree.root);
+    let (a, b) = (rng.below(100), rng.below(100));
+    let (lo, hi) = (cmp::min(a, b), cmp::max(a, b));
+    assert!(tree.range(lo..hi).map(|p| (p.key, p.value)).eq(model.range(lo..hi).map(|(&k, &v)| (k, v))));
+    assert!(tree.range((Bound::Excluded(lo), Bound::Included(hi))).map(|p| p.key)
+        .eq(model.range((Bound::Excluded(lo), Bound::Included(hi))).map(|(&k, _)| k)));
+    assert!(tree.iter().map(|p| (p.key, p.value)).eq(model.iter().map(|(&k, &v)| (k, v))));
+}
+
+/* 200 rounds of 500 random inserts, removes and lookups, with keys in a small range so that there are plenty of collisions and removals of keys that are really there. */
@@ -0,0 +1,295 @@
+// treemap.rs
+
+/* The word counting example in maps2.rs used a 'HashMap', and then had to copy everything into a vector and sort it to get any kind of order. A map built on a binary tree keeps its keys in order all the time. The 'Node<T>' from struct6.rs holds a single payload; for a map, each node holds a key and a value, and only the key is compared. Keys must implement 'Ord', like 'BTreeMap'. */
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem;
+
+type NodeBox<K,V> = Option<Box<Node<K,V>>>;
+
+struct Node<K,V> {
+    key: K,
+    value: V,
+    left: NodeBox<K,V>,
+    right: NodeBox<K,V>
+}
+
+struct TreeMap<K,V> {
+    root: NodeBox<K,V>,
+    len: usize
+}
+
+/* The heart of a map is a search for the place where a key is, or where it would go if it isn't there yet. That place is an 'Option' in the tree; either the root, or the 'left' or 'right' of some node. Returning a mutable reference to it is very useful, since then the caller can look at it, take the node out, or put a new node in.
+
+The loop is written a little awkwardly. We first look at the node and decide where to go next, and only then borrow the child mutably. The borrow checker isn't yet clever enough to accept the more obvious version where we match on 'node' and keep the mutable borrow of 'n' in the branch which moves down. The 'Borrow' bound is the usual trick which lets a 'TreeMap<String,_>' be searched with a '&str'. */
+
+// Find the place where 'key' is, or where it would go
+fn find<'a,K,V,Q>(mut node: &'a mut NodeBox<K,V>, key: &Q) -> &'a mut NodeBox<K,V>
+where K: Borrow<Q>, Q: Ord + ?Sized {
+    loop {
+        let order = match *node {
+            Some(ref n) => key.cmp(n.key.borrow()),
+            None => return node
+        };
+        match order {
+            Ordering::Less => node = &mut node.as_mut().unwrap().left,
+            Ordering::Greater => node = &mut node.as_mut().unwrap().right,
+            Ordering::Equal => return node
+        }
+    }
+}
+
+/* Now for the entry API. The expression 'map.entry(word).or_insert(0)' does a single search. If the key is there, the entry holds a mutable reference to its value. If it isn't, the entry holds the empty place where it belongs, along with the key; 'or_insert' can then put a new node there and hand back a reference to its value without searching again. The vacant entry also needs to bump the map's length, so it borrows that as well. Borrowing two different fields of the same struct mutably at the same time is fine. */
+
+enum Entry<'a,K,V> {
+    Occupied(&'a mut V),
+    Vacant(VacantEntry<'a,K,V>)
+}
+
+struct VacantEntry<'a,K,V> {
+    slot: &'a mut NodeBox<K,V>,
+    len: &'a mut usize,
+    key: K
+}
+
+impl <K: Ord,V> TreeMap<K,V> {
+    fn new() -> TreeMap<K,V> {
+        TreeMap{root: None, len: 0}
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn entry(&mut self, key: K) -> Entry<'_,K,V> {
+        let slot = find(&mut self.root, &key);
+        match *slot {
+            Some(ref mut n) => Entry::Occupied(&mut n.value),
+            None => Entry::Vacant(VacantEntry{slot: slot, len: &mut self.len, key: key})
+        }
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(v) => Some(mem::replace(v, value)),
+            Entry::Vacant(e) => {
+                e.insert(value);
+                None
+            }
+        }
+    }
+
+    fn get<Q>(&self, key: &Q) -> Option<&V>
+    where K: Borrow<Q>, Q: Ord + ?Sized {
+        let mut node = &self.root;
+        while let Some(ref n) = *node {
+            match key.cmp(n.key.borrow()) {
+                Ordering::Less => node = &n.left,
+                Ordering::Greater => node = &n.right,
+                Ordering::Equal => return Some(&n.value)
+            }
+        }
+        None
+    }
+
+    fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where K: Borrow<Q>, Q: Ord + ?Sized {
+        find(&mut self.root, key).as_mut().map(|n| &mut n.value)
+    }
+
+    fn contains_key<Q>(&self, key: &Q) -> bool
+    where K: Borrow<Q>, Q: Ord + ?Sized {
+        self.get(key).is_some()
+    }
+
+    fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where K: Borrow<Q>, Q: Ord + ?Sized {
+        let slot = find(&mut self.root, key);
+        let mut node = slot.take()?;
+        *slot = match (node.left.take(), node.right.take()) {
+            (None, child) | (child, None) => child,
+            (Some(left), Some(right)) => {
+                let (right, mut next) = remove_first(right);
+                next.left = Some(left);
+                next.right = right;
+                Some(next)
+            }
+        };
+        self.len -= 1;
+        Some(node.value)
+    }
+
+    fn iter(&self) -> Iter<'_,K,V> {
+        let mut iter = Iter{stack: Vec::new()};
+        iter.push_left(&self.root);
+        iter
+    }
+}
+
+/* 'insert' is just an entry which replaces the value if it is already there, and gives back the old one like 'HashMap::insert'. 'get_mut' and 'remove' use 'find' as well. A node with two children is replaced by the first node of its right subtree. (See avl.rs for the same thing done with 'Tree<T>'.) */
+
+fn remove_first<K,V>(mut node: Box<Node<K,V>>) -> (NodeBox<K,V>, Box<Node<K,V>>) {
+    match node.left.take() {
+        None => (node.right.take(), node),
+        Some(left) => {
+            let (left, first) = remove_first(left);
+            node.left = left;
+            (Some(node), first)
+        }
+    }
+}
+
+/* 'get_or_insert' fills in an empty 'Option' and returns a mutable reference to what's inside, which is exactly what we need. Note the lifetimes: the references handed back live as long as the borrow of the map made by 'entry', so 'or_insert' can be used as the target of '+=' just like with 'HashMap'. */
+
+impl <'a,K,V> VacantEntry<'a,K,V> {
+    fn insert(self, value: V) -> &'a mut V {
+        *self.len += 1;
+        let node = self.slot.get_or_insert(Box::new(Node{key: self.key, value: value, left: None, right: None}));
+        &mut node.value
+    }
+}
+
+impl <'a,K,V> Entry<'a,K,V> {
+    fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    fn or_insert_with<F: FnOnce() -> V>(self, f: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(v) => v,
+            Entry::Vacant(e) => e.insert(f())
+        }
+    }
+
+    fn or_default(self) -> &'a mut V
+    where V: Default {
+        self.or_insert_with(V::default)
+    }
+
+    fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(ref mut v) = self {
+            f(v);
+        }
+        self
+    }
+}
+
+/* Iterating over the map in order needs a stack of nodes which have yet to be visited. After visiting a node, we go to its right child and then all the way down to the left. */
+
+struct Iter<'a,K,V> {
+    stack: Vec<&'a Node<K,V>>
+}
+
+impl <'a,K,V> Iter<'a,K,V> {
+    fn push_left(&mut self, mut node: &'a NodeBox<K,V>) {
+        while let Some(ref n) = *node {
+            self.stack.push(n);
+            node = &n.left;
+        }
+    }
+}
+
+impl <'a,K,V> Iterator for Iter<'a,K,V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let node = self.stack.pop()?;
+        self.push_left(&node.right);
+        Some((&node.key, &node.value))
+    }
+}
+
+impl <'a,K: Ord,V> IntoIterator for &'a TreeMap<K,V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a,K,V>;
+
+    fn into_iter(self) -> Iter<'a,K,V> {
+        self.iter()
+    }
+}
+
+impl <K: Ord,V> FromIterator<(K,V)> for TreeMap<K,V> {
+    fn from_iter<I: IntoIterator<Item = (K,V)>>(iter: I) -> TreeMap<K,V> {
+        let mut map = TreeMap::new();
+        for (k, v) in iter {
+            map.insert(k, v);
+        }
+        map
+    }
+}
+
+impl <K: Ord + fmt::Debug, V: fmt::Debug> fmt::Debug for TreeMap<K,V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/* Here's the word counting code from maps2.rs, unchanged except for the type of the map. */
+
+let text = "It was the best of times, it was the worst of times, it was the age of wisdom, it was the age of foolishness";
+let mut map = TreeMap::new();
+for s in text.split(|c: char| ! c.is_alphabetic()) {
+    let word = s.to_lowercase();
+    let count = map.entry(word).or_insert(0);
+    *count += 1;
+}
+println!("{:?}", map);
+// {"": 3, "age": 2, "best": 1, "foolishness": 1, "it": 4, "of": 4, "the": 4, "times": 2, "was": 4, "wisdom": 1, "worst": 1}
+
+/* The words come out in alphabetical order with no sorting step. (And there's our old friend the empty word, from the places where two punctuation characters are next to each other.) Iterating gives pairs of references: */
+
+for (word, count) in &map {
+    print!("{}:{} ", word, count);
+}
+// :3 age:2 best:1 foolishness:1 it:4 of:4 the:4 times:2 was:4 wisdom:1 worst:1
+
+// The rest of the entry API, and the usual map methods
+let mut map: TreeMap<&str,i32> = vec![("one", 1), ("two", 2), ("three", 3)].into_iter().collect();
+println!("{:?}", map.insert("two", 20));
+println!("{:?}", map.get("two"));
+// Some(2)
+// Some(20)
+
+map.entry("one").and_modify(|v| *v += 10).or_insert(0);
+map.entry("four").and_modify(|v| *v += 10).or_insert(4);
+*map.entry("five").or_default() += 5;
+if let Some(v) = map.get_mut("three") {
+    *v *= 100;
+}
+println!("{:?}", map);
+// {"five": 5, "four": 4, "one": 11, "three": 300, "two": 20}
+
+println!("{:?} {:?} {}", map.remove("two"), map.remove("two"), map.len());
+// Some(20) None 4
+
+/* This map is not balanced, like the tree in struct6.rs. Keeping it balanced would mean rotating nodes on the way back up after an insertion, which is easy when insertion is recursive as in avl.rs, but the vacant entry has already given away the only mutable reference to the tree's insides. For word counts this doesn't matter much, since words don't usually arrive in alphabetical order. It still agrees with 'BTreeMap' on a long run of random operations: */
+
+use std::collections::BTreeMap;
+let mut seed: u64 = 0x2545F4914F6CDD1D;
+let mut random = move |n: u64| {
+    seed ^= seed << 13;
+    seed ^= seed >> 7;
+    seed ^= seed << 17;
+    seed % n
+};
+let mut tree = TreeMap::new();
+let mut model = BTreeMap::new();
+for _ in 0..100000 {
+    let key = random(200);
+    match random(4) {
+        0 => assert_eq!(tree.remove(&key), model.remove(&key)),
+        1 => {
+            *tree.entry(key).or_insert(0) += 1;
+            *model.entry(key).or_insert(0) += 1;
+        },
+        _ => {
+            let value = random(1000);
+            assert_eq!(tree.insert(key, value), model.insert(key, value));
+        }
+    }
+    assert_eq!(tree.len(), model.len());
+}
+assert!(tree.iter().eq(model.iter()));
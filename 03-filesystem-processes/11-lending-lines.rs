@@ -0,0 +1,289 @@
+// lines.rs
+
+/* The 'Lines' struct in file5.rs reuses one buffer for every line, but its 'next' isn't 'Iterator::next', so none of the iterator methods work with it. They can't: an 'Iterator' hands out items which live independently of the iterator, so 'collect' can keep them all. Our lines are all borrowed from the same buffer, and each one must be given back before the next can be read.
+
+An iterator like that is called a "lending" iterator, since it lends out each item rather than giving it away. There's no such trait in the standard library, but we can write one, along with the handful of adaptors which make iterators pleasant to use.
+
+The obvious way to say "the item borrows from the iterator" is a generic associated type, 'type Item<'a> where Self: 'a'. Unfortunately that runs into a limit of the compiler: as soon as a closure has to accept an item of any lifetime (which 'filter' and 'map' need), the compiler insists that the iterator lives for ''static', so 'Lines' could no longer read from a borrowed '&[u8]'. The usual way around this is to put the lifetime on a little trait of its own. 'Lend<'a>' says what the item type is for a borrow of lifetime ''a', and a lending iterator has to implement it for every lifetime, which is what 'for<'a>' means. The 'Item' type alias just saves typing. */
+
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+
+// The type of item lent out for a borrow of lifetime 'a
+trait Lend<'a> {
+    type Item;
+}
+
+type Item<'a,I> = <I as Lend<'a>>::Item;
+
+/* The trait itself splits 'next' into two. 'advance' moves on to the next item, and 'get' looks at the current one. This seems fussy, but it is what makes 'filter' possible. A filter has to keep fetching items until one passes the test, and then return that one. Written with 'next', that means returning a borrow from inside a loop which borrows mutably again on the next time round, and the borrow checker can't yet see that this is safe. (This is the case which "non-lexical lifetimes" didn't fix.) With 'get' borrowing immutably, there is no problem.
+
+'next' does both steps. The adaptors have default methods, like those of 'Iterator'. 'map' is different from the others: it turns each borrowed item into a value of its own, so the result is an ordinary 'Iterator', and everything from 'collect' to 'sum' works on it. */
+
+trait LendingIterator: for<'a> Lend<'a> {
+    // Move on to the next item
+    fn advance(&mut self);
+
+    // The current item, or 'None' when there are no more
+    fn get(&self) -> Option<Item<'_,Self>>;
+
+    fn next(&mut self) -> Option<Item<'_,Self>> {
+        self.advance();
+        self.get()
+    }
+
+    fn filter<P>(self, pred: P) -> Filter<Self,P>
+    where Self: Sized, P: FnMut(&Item<'_,Self>) -> bool {
+        Filter{iter: self, pred: pred}
+    }
+
+    fn map<B,F>(self, f: F) -> Map<Self,F>
+    where Self: Sized, F: FnMut(Item<'_,Self>) -> B {
+        Map{iter: self, f: f}
+    }
+
+    fn for_each<F>(mut self, mut f: F)
+    where Self: Sized, F: FnMut(Item<'_,Self>) {
+        while let Some(item) = self.next() {
+            f(item);
+        }
+    }
+
+    fn try_fold<B,E,F>(&mut self, init: B, mut f: F) -> Result<B,E>
+    where F: FnMut(B, Item<'_,Self>) -> Result<B,E> {
+        let mut acc = init;
+        while let Some(item) = self.next() {
+            acc = f(acc, item)?;
+        }
+        Ok(acc)
+    }
+}
+
+struct Filter<I,P> {
+    iter: I,
+    pred: P
+}
+
+impl <'a,I: LendingIterator,P> Lend<'a> for Filter<I,P> {
+    type Item = Item<'a,I>;
+}
+
+impl <I,P> LendingIterator for Filter<I,P>
+where I: LendingIterator, P: FnMut(&Item<'_,I>) -> bool {
+    fn advance(&mut self) {
+        loop {
+            self.iter.advance();
+            match self.iter.get() {
+                Some(ref item) if ! (self.pred)(item) => continue,
+                _ => break
+            }
+        }
+    }
+
+    fn get(&self) -> Option<Item<'_,I>> {
+        self.iter.get()
+    }
+}
+
+struct Map<I,F> {
+    iter: I,
+    f: F
+}
+
+impl <B,I,F> Iterator for Map<I,F>
+where I: LendingIterator, F: FnMut(Item<'_,I>) -> B {
+    type Item = B;
+
+    fn next(&mut self) -> Option<B> {
+        self.iter.next().map(&mut self.f)
+    }
+}
+
+/* Now for the lines themselves. Not every file ends its lines with '\n'; Windows files use '\r\n', and 'find -print0' separates file names with a NUL byte, since that's the only character which can't appear in one. And not every file is valid UTF-8. Sometimes a bad byte is a real error, and sometimes we'd rather just see the rest of the line, with '�' where the bad bytes were. Both of these are settings, given in the same way as 'WordCounter' in wordcount.rs.
+
+The line is read as bytes with 'read_until', which doesn't care about UTF-8, and only then turned into text. 'String::from_utf8_lossy' only has to allocate when it finds bad bytes; for a good line it borrows. Either way the text is copied into the 'line' buffer, so once both buffers are big enough for the longest line, no more memory is allocated at all. Unlike the old 'trim_right', only the terminator is removed, so spaces at the end of a line are kept. */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Terminator {
+    Newline,
+    CrLf,
+    Nul
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Utf8 {
+    Lossy,
+    Strict
+}
+
+struct Lines<R> {
+    reader: io::BufReader<R>,
+    terminator: Terminator,
+    utf8: Utf8,
+    bytes: Vec<u8>,
+    line: String,
+    number: usize,
+    state: State
+}
+
+enum State {
+    Line,
+    Failed(io::ErrorKind, String),
+    Done
+}
+
+impl <R: Read> Lines<R> {
+    fn new(r: R) -> Lines<R> {
+        Lines{
+            reader: io::BufReader::new(r),
+            terminator: Terminator::Newline,
+            utf8: Utf8::Strict,
+            bytes: Vec::new(),
+            line: String::new(),
+            number: 0,
+            state: State::Line
+        }
+    }
+
+    fn terminator(mut self, terminator: Terminator) -> Lines<R> {
+        self.terminator = terminator;
+        self
+    }
+
+    fn utf8(mut self, utf8: Utf8) -> Lines<R> {
+        self.utf8 = utf8;
+        self
+    }
+
+    // The number of the current line, starting at 1
+    fn number(&self) -> usize {
+        self.number
+    }
+
+    fn read(&mut self) -> io::Result<bool> {
+        self.bytes.clear();
+        let end = if self.terminator == Terminator::Nul {b'\0'} else {b'\n'};
+        if self.reader.read_until(end, &mut self.bytes)? == 0 {
+            return Ok(false);
+        }
+        self.number += 1;
+        // the last line might not have a terminator
+        if self.bytes.last() == Some(&end) {
+            self.bytes.pop();
+            if self.terminator == Terminator::CrLf && self.bytes.last() == Some(&b'\r') {
+                self.bytes.pop();
+            }
+        }
+        self.line.clear();
+        match self.utf8 {
+            Utf8::Lossy => self.line.push_str(&String::from_utf8_lossy(&self.bytes)),
+            Utf8::Strict => match std::str::from_utf8(&self.bytes) {
+                Ok(s) => self.line.push_str(s),
+                Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData,
+                    format!("line {}: invalid UTF-8 at byte {}", self.number, e.valid_up_to())))
+            }
+        }
+        Ok(true)
+    }
+}
+
+/* The item is an 'io::Result<&str>', as before. Since 'get' can be called more than once for the same item, an error has to be kept around, and 'io::Error' can't be cloned; so we keep its kind and message, and make a new error from them each time. After an error there are no more lines, since a reader which has failed once can't be trusted to carry on from the right place. */
+
+impl <'a,R> Lend<'a> for Lines<R> {
+    type Item = io::Result<&'a str>;
+}
+
+impl <R: Read> LendingIterator for Lines<R> {
+    fn advance(&mut self) {
+        if let State::Line = self.state {
+            self.state = match self.read() {
+                Ok(true) => State::Line,
+                Ok(false) => State::Done,
+                Err(e) => State::Failed(e.kind(), e.to_string())
+            };
+        } else {
+            // after an error, there are no more lines
+            self.state = State::Done;
+        }
+    }
+
+    fn get(&self) -> Option<io::Result<&str>> {
+        match self.state {
+            State::Line => Some(Ok(&self.line)),
+            State::Failed(kind, ref msg) => Some(Err(io::Error::new(kind, msg.clone()))),
+            State::Done => None
+        }
+    }
+}
+
+/* The loop from file5.rs works as it did. But splitting 'next' in two has a bonus: 'get' only borrows 'lines' immutably, so we can ask for the line number while we hold the line, which is impossible with a '&mut self' method. */
+
+let text = "# config\nname = bob\n\n# more\nage = 42\r\nlast";
+let mut lines = Lines::new(text.as_bytes());
+while let Some(line) = {lines.advance(); lines.get()} {
+    println!("{} {:?}", lines.number(), line?);
+}
+// 1 "# config"
+// 2 "name = bob"
+// 3 ""
+// 4 "# more"
+// 5 "age = 42\r"
+// 6 "last"
+
+/* The '\r' stays, since we asked for plain '\n' lines. With 'CrLf' it goes, and here the comments and blank lines are filtered out. 'matches!' is handy for tests like this, where an error should always get through the filter. */
+
+let lines = Lines::new(text.as_bytes()).terminator(Terminator::CrLf);
+lines.filter(|line| ! matches!(line, Ok(s) if s.is_empty() || s.starts_with('#')))
+    .for_each(|line| println!("{:?}", line));
+// Ok("name = bob")
+// Ok("age = 42")
+// Ok("last")
+
+/* 'map' is where each line becomes something which lasts, so only the owned strings it makes are allocated. Collecting an iterator of 'io::Result' into an 'io::Result<Vec<_>>' stops at the first error, as in docs.rs. */
+
+let pairs: io::Result<Vec<(String,String)>> = Lines::new(text.as_bytes())
+    .terminator(Terminator::CrLf)
+    .filter(|line| ! matches!(line, Ok(s) if s.is_empty() || s.starts_with('#')))
+    .map(|line| {
+        let line = line?;
+        let (k, v) = line.split_once('=').unwrap_or((line, ""));
+        Ok((k.trim().to_string(), v.trim().to_string()))
+    })
+    .collect();
+println!("{:?}", pairs);
+// Ok([("name", "bob"), ("age", "42"), ("last", "")])
+
+// Count the characters in a file, without collecting the lines
+fn char_count(filename: &str) -> io::Result<usize> {
+    let file = File::open(filename)?;
+    Lines::new(file).try_fold(0, |n, line| Ok(n + line?.chars().count()))
+}
+
+/* Here is what happens with a bad byte in each mode. The error says which line it was on, and how far into the line it got. */
+
+let bad: &[u8] = b"good\nbad \xff\xfe here\nafter\n";
+Lines::new(bad).utf8(Utf8::Lossy).for_each(|line| println!("{:?}", line));
+// Ok("good")
+// Ok("bad �� here")
+// Ok("after")
+Lines::new(bad).for_each(|line| println!("{:?}", line));
+// Ok("good")
+// Err(Custom { kind: InvalidData, error: "line 2: invalid UTF-8 at byte 4" })
+
+let names: &[u8] = b"a file\0another file.txt\0";
+Lines::new(names).terminator(Terminator::Nul).for_each(|line| println!("{:?}", line));
+// Ok("a file")
+// Ok("another file.txt")
+
+/* To check that there really are no allocations after the first line, we can look at the capacity of the buffers for every line of a big text; it never changes. (That peeks inside 'Lines', so it only works in the same module.) */
+
+let big: String = (0..10000).map(|i| format!("line {}\n", i % 100)).collect();
+let mut lines = Lines::new(big.as_bytes());
+let mut capacities = std::collections::HashSet::new();
+while let Some(line) = {lines.advance(); lines.get()} {
+    line?;
+    capacities.insert((lines.bytes.capacity(), lines.line.capacity()));
+}
+println!("{:?}", capacities);
+// {(8, 8)}
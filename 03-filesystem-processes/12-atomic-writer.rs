@@ -0,0 +1,180 @@
+// atomic.rs
+
+/* 'write_out' in file6.rs writes straight to the file, with no buffering, which file6.rs itself warns is slow. There's a worse problem too. 'File::create' empties the file first, so if the program crashes, or returns an error halfway through writing, the old contents are gone and the new ones are only half there. For a configuration file or a saved document, that's about the worst thing which can happen.
+
+The usual cure is to write somewhere else, and only replace the real file once the new one is complete. Renaming a file over another one is atomic: anyone opening the file sees either all of the old one or all of the new one, never a mixture. But a rename only works within one filesystem, so the temporary file goes in the same directory as the target. Its name starts with '.', so it's hidden on Unix, and includes the process id and a counter, so that two programs (or two threads) writing the same file don't trip over each other. 'create_new' refuses to open a file which already exists, rather than quietly sharing it; in the unlikely case of a leftover file with the same name, we try another name. */
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// A file which only replaces 'target' when it is committed
+struct AtomicWriter {
+    target: PathBuf,
+    temp: PathBuf,
+    out: Option<io::BufWriter<File>>,
+    committed: bool
+}
+
+// makes temporary names unique within this process
+static TEMP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/* If the target already exists, the new file gets its permissions, so that replacing a file which only its owner could read doesn't suddenly make it readable by everyone. The 'AtomicWriter' is made as soon as the temporary file exists, before the permissions are set; then if setting them fails, its 'drop' (see below) removes the file, and we don't leave a stray '.tmp' behind. */
+
+impl AtomicWriter {
+    fn create<P: AsRef<Path>>(path: P) -> io::Result<AtomicWriter> {
+        let target = path.as_ref().to_path_buf();
+        let name = target.file_name()
+            .ok_or(io::Error::new(io::ErrorKind::InvalidInput, format!("{}: not a file name", target.display())))?
+            .to_string_lossy().into_owned();
+        let mut tries = 0;
+        loop {
+            let n = TEMP_COUNT.fetch_add(1, Ordering::SeqCst);
+            let temp = target.with_file_name(format!(".{}.{}.{}.tmp", name, std::process::id(), n));
+            match OpenOptions::new().write(true).create_new(true).open(&temp) {
+                Ok(file) => {
+                    // from here on, dropping 'writer' removes the temporary file
+                    let writer = AtomicWriter{target: target, temp: temp, out: Some(io::BufWriter::new(file)), committed: false};
+                    // keep the permissions of the file being replaced
+                    if let Ok(meta) = fs::metadata(&writer.target) {
+                        writer.out.as_ref().unwrap().get_ref().set_permissions(meta.permissions())?;
+                    }
+                    return Ok(writer);
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists && tries < 10 => tries += 1,
+                Err(e) => return Err(io::Error::new(e.kind(), format!("{}: {}", temp.display(), e)))
+            }
+        }
+    }
+    ...
+}
+
+/* 'commit' is where the new file takes the place of the old one, and the order of the steps matters.
+
+First the buffer has to be flushed. 'BufWriter::into_inner' does that and gives back the file; if the flush fails we get the error (it comes wrapped up with the writer, which 'into_error' throws away). Then 'sync_all' waits until the data has really reached the disk, rather than just the operating system's cache. Without it, a power cut just after the rename could leave the new name pointing at a file whose contents were never written. Then the rename. And on Unix, a rename is a change to the directory, which has its own cache, so the directory is synced as well.
+
+'commit' takes 'self' by value, so that nothing can be written after it. But 'AtomicWriter' implements 'Drop', and Rust won't let us move a field out of a value which has a 'drop' method, since 'drop' would then see a half-empty struct. That's why 'out' is an 'Option': 'take' moves the writer out and leaves 'None' in its place. */
+
+fn commit(mut self) -> io::Result<()> {
+    let out = self.out.take().unwrap();
+    let file = out.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&self.temp, &self.target)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", self.target.display(), e)))?;
+    self.committed = true;
+    // the rename itself is only safe once the directory is on disk
+    #[cfg(unix)]
+    {
+        let dir = match self.target.parent() {
+            Some(p) if p != Path::new("") => p,
+            _ => Path::new(".")
+        };
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/* Writing just passes everything on to the 'BufWriter'. ('out' is only 'None' inside 'commit' and 'drop', so 'unwrap' is safe here.)
+
+If the writer is dropped without being committed, because of an error, an early return, or a panic, then 'drop' removes the temporary file and the target is left just as it was. The file is closed first, since Windows won't remove a file which is still open. If 'commit' itself fails, the temporary file is removed too, since 'committed' is only set once the rename has worked. */
+
+impl Write for AtomicWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.out.as_mut().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.as_mut().unwrap().flush()
+    }
+}
+
+impl Drop for AtomicWriter {
+    fn drop(&mut self) {
+        // close the file before removing it
+        self.out = None;
+        if ! self.committed {
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
+
+// 'write_out' hardly changes, apart from the commit at the end
+fn write_out(f: &str) -> io::Result<()> {
+    let mut out = AtomicWriter::create(f)?;
+    writeln!(out, "answer is {}", 42)?;
+    out.commit()
+}
+
+/* Now to check that failing halfway really does leave the old file alone. This function writes 'n' lines, but can be told to fail at one of them, which is just what would happen if a real write failed: '?' returns early, and the writer is dropped. */
+
+// Write 'n' lines, failing at line 'fail'
+fn write_lines(path: &str, n: usize, fail: Option<usize>) -> io::Result<()> {
+    let mut out = AtomicWriter::create(path)?;
+    for i in 0..n {
+        if Some(i) == fail {
+            return Err(io::Error::other(format!("failed at line {}", i)));
+        }
+        writeln!(out, "line {}", i)?;
+    }
+    out.commit()
+}
+
+// The temporary files in a directory, to check that none are left behind
+fn temp_files(dir: &str) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir).unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|n| n.ends_with(".tmp"))
+        .collect();
+    names.sort();
+    names
+}
+
+write_out("test.txt")?;
+println!("{:?}", fs::read_to_string("test.txt")?);
+// "answer is 42\n"
+
+write_lines("lines.txt", 1000, None)?;
+println!("{:?}", write_lines("lines.txt", 100000, Some(50000)));
+println!("{} {:?}", fs::read_to_string("lines.txt")?.lines().count(), temp_files("."));
+// Err(Custom { kind: Other, error: "failed at line 50000" })
+// 1000 []
+
+/* The second call had written 50000 lines (and the 'BufWriter' had long since passed most of them on to the temporary file) when it failed; but 'lines.txt' still has the thousand lines from the first call, and the temporary file is gone.
+
+A panic unwinds the stack, dropping everything on the way, so it tidies up in the same way. 'catch_unwind' stops the panic so that we can look at the result. */
+
+let res = std::panic::catch_unwind(|| {
+    let mut out = AtomicWriter::create("lines.txt").unwrap();
+    for i in 0..100000 {
+        if i == 60000 {
+            panic!("oops");
+        }
+        writeln!(out, "line {}", i).unwrap();
+    }
+    out.commit().unwrap();
+});
+println!("{} {} {:?}", res.is_err(), fs::read_to_string("lines.txt")?.lines().count(), temp_files("."));
+// thread 'main' panicked at src/main.rs:...:
+// oops
+// true 1000 []
+
+{
+    let mut out = AtomicWriter::create("new.txt")?;
+    writeln!(out, "never seen")?;
+    println!("{:?}", temp_files("."));
+}
+println!("{} {:?}", Path::new("new.txt").exists(), temp_files("."));
+// [".new.txt.11295.4.tmp"]
+// false []
+
+// A commit which fails, because the target is a directory
+fs::create_dir_all("adir")?;
+let mut out = AtomicWriter::create("adir")?;
+writeln!(out, "x")?;
+println!("{:?} {:?}", out.commit().map_err(|e| e.to_string()), temp_files("."));
+// Err("adir: Is a directory (os error 21)") []
+
+/* One thing 'drop' can't do anything about is the program being killed outright, or the power going off. Then a temporary file may be left behind, but the target is still whole, which is the point. The hidden '.tmp' files are easy to find and clean up afterwards. */
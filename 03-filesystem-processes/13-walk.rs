@@ -0,0 +1,374 @@
+// walk.rs
+
+/* 'dump_dir' in file10.rs only looks at one directory, and what it looks for ("extension rs, larger than 1024 bytes") is written into the loop. What we usually want is to go down through all the directories below, like the Unix 'find' command, and to say what we're looking for separately. So let's write 'walk', an iterator over everything below a directory.
+
+Each entry comes with its path, how deep it is (the contents of the starting directory are at depth 1), and its 'Metadata', since nearly every question we might ask about a file needs it, and we've already had to get it to know whether the entry is a directory. */
+
+use std::cmp::Ordering;
+use std::fs::{self, Metadata};
+use std::io;
+use std::ops::RangeBounds;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// A file or directory found by 'walk'
+#[derive(Debug)]
+struct Entry {
+    path: PathBuf,
+    depth: usize,
+    metadata: Metadata
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    File,
+    Dir,
+    Symlink
+}
+
+impl Entry {
+    fn kind(&self) -> Kind {
+        let t = self.metadata.file_type();
+        if t.is_symlink() {
+            Kind::Symlink
+        } else if t.is_dir() {
+            Kind::Dir
+        } else {
+            Kind::File
+        }
+    }
+}
+
+/* The walk goes depth first: each directory's contents come straight after it. That needs a stack, and each item on the stack is a directory which we haven't finished with yet, holding the entries still to be visited. When a directory runs out of entries it comes off the stack, and we carry on with its parent.
+
+The stack also gives us the answer to symbolic links. A link to a directory can point back up the tree ('ln -s .. up'), and a walk which follows it will go round forever. The directories on the stack are exactly the ones we are inside, so a loop is a directory which is already on the stack. Comparing paths isn't enough, since the link's path is different from the real one; 'fs::canonicalize' resolves all the links and gives the real path, so that's what each frame remembers. By default links aren't followed at all, and then there can't be any loops.
+
+The options are set with methods which take 'self' and return it, as with 'Lines' in lines.rs. The filters are closures, so they are boxed, as with the commands in 'Cli'. */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Sort {
+    Name,
+    Size,
+    Modified
+}
+
+type Filter = Box<dyn Fn(&Entry) -> bool>;
+
+// The entries of one directory which haven't been visited yet
+struct Frame {
+    entries: std::vec::IntoIter<io::Result<Entry>>,
+    // the real path of the directory, for finding loops
+    real: PathBuf
+}
+
+struct Walk {
+    root: Option<PathBuf>,
+    frames: Vec<Frame>,
+    max_depth: usize,
+    follow_links: bool,
+    sort: Option<Sort>,
+    filters: Vec<Filter>,
+    // an error to give out after the entry it belongs to
+    pending: Option<io::Error>
+}
+
+fn walk<P: AsRef<Path>>(root: P) -> Walk {
+    Walk{
+        root: Some(root.as_ref().to_path_buf()),
+        frames: Vec::new(),
+        max_depth: usize::MAX,
+        follow_links: false,
+        sort: None,
+        filters: Vec::new(),
+        pending: None
+    }
+}
+
+impl Walk {
+    fn max_depth(mut self, depth: usize) -> Walk {
+        self.max_depth = depth;
+        self
+    }
+
+    fn follow_links(mut self, yes: bool) -> Walk {
+        self.follow_links = yes;
+        self
+    }
+
+    fn sort(mut self, sort: Sort) -> Walk {
+        self.sort = Some(sort);
+        self
+    }
+
+    fn filter<F>(mut self, f: F) -> Walk
+    where F: Fn(&Entry) -> bool + 'static {
+        self.filters.push(Box::new(f));
+        self
+    }
+    ...
+}
+
+/* A directory is read all at once when we get to it, so that its entries can be sorted. That is sorting within each directory, which is what 'ls' does; sorting everything would mean reading the whole tree before giving out the first entry.
+
+'read_dir' can fail, and so can each entry, and so can getting the metadata, as file10.rs explained. None of these should stop the walk. One directory we can't read says nothing about the others, so every error becomes an item of its own, with the path added to say where it happened. 'symlink_metadata' describes a link itself, rather than what it points to; when following links we want the target, unless there's nothing there, in which case it is still worth seeing the broken link. */
+
+fn with_path(path: &Path, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", path.display(), e))
+}
+
+fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+    if self.follow_links {
+        // a link to nothing is still worth seeing, as a link
+        fs::metadata(path).or_else(|_| fs::symlink_metadata(path))
+    } else {
+        fs::symlink_metadata(path)
+    }
+}
+
+// Read a whole directory, so that it can be sorted
+fn read(&self, dir: &Path, depth: usize) -> io::Result<Vec<io::Result<Entry>>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| with_path(dir, e))? {
+        entries.push(entry.map_err(|e| with_path(dir, e)).and_then(|entry| {
+            let path = entry.path();
+            let metadata = self.metadata(&path).map_err(|e| with_path(&path, e))?;
+            Ok(Entry{path: path, depth: depth, metadata: metadata})
+        }));
+    }
+    if let Some(sort) = self.sort {
+        entries.sort_by(|a, b| match (a, b) {
+            (Ok(a), Ok(b)) => compare(sort, a, b),
+            // errors first
+            (Err(_), Ok(_)) => Ordering::Less,
+            (Ok(_), Err(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => Ordering::Equal
+        });
+    }
+    Ok(entries)
+}
+
+fn push(&mut self, dir: &Path, depth: usize) -> io::Result<()> {
+    let real = fs::canonicalize(dir).map_err(|e| with_path(dir, e))?;
+    let entries = self.read(dir, depth)?;
+    self.frames.push(Frame{entries: entries.into_iter(), real: real});
+    Ok(())
+}
+
+// Entries which are equal by size or time are sorted by name
+fn compare(sort: Sort, a: &Entry, b: &Entry) -> Ordering {
+    let by = match sort {
+        Sort::Name => Ordering::Equal,
+        // biggest and newest first
+        Sort::Size => b.metadata.len().cmp(&a.metadata.len()),
+        Sort::Modified => b.metadata.modified().ok().cmp(&a.metadata.modified().ok())
+    };
+    by.then(a.path.cmp(&b.path))
+}
+
+/* Now 'next'. The root directory is only read when the walk starts, so that creating a 'Walk' can't fail; if the root can't be read, that's the first item. After that we take the next entry from the top of the stack. A directory gets pushed, unless it's too deep or it's a loop, and then the entry is given out if it passes all the filters. A directory which doesn't pass is still walked, just as 'find -name "*.rs"' still looks inside directories which aren't called "*.rs".
+
+A directory which can't be read is still an entry, and it's still there; it's only what's inside it that we can't see. So it is given out as usual, and the error is kept in 'pending' and given out by the next call. If the directory doesn't pass the filters, the error comes out straight away instead. */
+
+impl Iterator for Walk {
+    type Item = io::Result<Entry>;
+
+    fn next(&mut self) -> Option<io::Result<Entry>> {
+        if let Some(e) = self.pending.take() {
+            return Some(Err(e));
+        }
+        if let Some(root) = self.root.take() {
+            if let Err(e) = self.push(&root, 1) {
+                return Some(Err(e));
+            }
+        }
+        loop {
+            let entry = match self.frames.last_mut()?.entries.next() {
+                Some(Ok(entry)) => entry,
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.frames.pop();
+                    continue;
+                }
+            };
+            if entry.metadata.is_dir() && entry.depth < self.max_depth {
+                if self.follow_links {
+                    // a directory which is also one of its own parents is a loop
+                    // (if it has no real path, 'push' fails and says so)
+                    let real = fs::canonicalize(&entry.path).ok();
+                    if let Some(f) = self.frames.iter().find(|f| Some(&f.real) == real.as_ref()) {
+                        return Some(Err(io::Error::other(format!("{}: symlink loop back to {}", entry.path.display(), f.real.display()))));
+                    }
+                }
+                // the directory is still an entry, even if it can't be read
+                if let Err(e) = self.push(&entry.path, entry.depth + 1) {
+                    self.pending = Some(e);
+                }
+            }
+            if self.filters.iter().all(|f| f(&entry)) {
+                return Some(Ok(entry));
+            }
+            if let Some(e) = self.pending.take() {
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+/* Each call to 'filter' adds another test, and an entry has to pass all of them. The filters themselves are made by little functions which return closures. 'impl Fn(&Entry) -> bool' says "some closure type, which you don't need to know"; each closure captures what it needs with 'move'. 'size' takes any kind of range, so '1025..' means "more than 1024 bytes" and '..100' means "less than 100". 'not' and 'any' are filters made out of other filters, for the cases which "all of them" doesn't cover. */
+
+fn extension(ext: &str) -> impl Fn(&Entry) -> bool {
+    let ext = ext.to_string();
+    move |e| e.path.extension().map(|x| x == ext.as_str()).unwrap_or(false)
+}
+
+fn size<R: RangeBounds<u64>>(range: R) -> impl Fn(&Entry) -> bool {
+    move |e| range.contains(&e.metadata.len())
+}
+
+fn modified_since(time: SystemTime) -> impl Fn(&Entry) -> bool {
+    move |e| e.metadata.modified().map(|t| t >= time).unwrap_or(false)
+}
+
+fn kind(kind: Kind) -> impl Fn(&Entry) -> bool {
+    move |e| e.kind() == kind
+}
+
+fn not<F>(f: F) -> impl Fn(&Entry) -> bool
+where F: Fn(&Entry) -> bool {
+    move |e| ! f(e)
+}
+
+fn any(filters: Vec<Filter>) -> impl Fn(&Entry) -> bool {
+    move |e| filters.iter().any(|f| f(e))
+}
+
+/* A glob is the shell's kind of pattern, matched against the file name: '*' matches any number of characters, and '?' matches exactly one. Matching is naturally recursive. A '*' tries matching the rest of the pattern against every possible tail of the name, including the whole of it (so '*' can match nothing) and the empty tail. 'split_first' gives the first item of a slice and the rest of it, or 'None' if it's empty. */
+
+fn glob(pattern: &str) -> impl Fn(&Entry) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    move |e| match e.path.file_name() {
+        Some(name) => glob_match(&pattern, &name.to_string_lossy().chars().collect::<Vec<_>>()),
+        None => false
+    }
+}
+
+// '*' matches any number of characters, '?' matches one
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((&'*', rest)) => (0..=name.len()).any(|i| glob_match(rest, &name[i..])),
+        Some((&p, rest)) => match name.split_first() {
+            Some((&c, name)) if p == '?' || p == c => glob_match(rest, name),
+            _ => false
+        }
+    }
+}
+
+let chars = |s: &str| s.chars().collect::<Vec<_>>();
+for p in &["*.rs", "main.rs", "m??n.rs", "*", "*a*", "x*y"] {
+    print!("{} ", glob_match(&chars(p), &chars("main.rs")));
+}
+// true true true true true false
+
+/* Here's 'dump_dir' again. It only looks at one level, as before, but now it says what it's looking for in one place, and an error with one entry doesn't stop the others being listed. */
+
+// All files with extension ".rs" and size greater than 1024 bytes:
+fn dump_dir(dir: &str) {
+    for entry in walk(dir).max_depth(1).filter(extension("rs")).filter(size(1025..)) {
+        match entry {
+            Ok(e) => println!("{} length {}", e.path.display(), e.metadata.len()),
+            Err(e) => println!("error: {}", e)
+        }
+    }
+}
+
+/* To try out the rest, here's a little tree with some links in it: 'src/bin/up' points back up at 'src', 'src/docs' points across to 'docs', and 'docs/broken' points at nothing. */
+
+fn show(w: Walk) {
+    for entry in w {
+        match entry {
+            Ok(e) => println!("{} {} {:?} {}", e.depth, e.path.display(), e.kind(), e.metadata.len()),
+            Err(e) => println!("error: {}", e)
+        }
+    }
+}
+
+// $ mkdir -p tree/src/bin tree/docs
+// $ ln -s .. tree/src/bin/up
+// $ ln -s ../docs tree/src/docs
+// $ ln -s nowhere tree/docs/broken
+// (and some files)
+
+show(walk("tree").sort(Sort::Name));
+// 1 tree/Cargo.toml File 10
+// 1 tree/docs Dir 4096
+// 2 tree/docs/broken Symlink 7
+// 2 tree/docs/guide.md File 3000
+// 2 tree/docs/notes.txt File 2
+// 1 tree/src Dir 4096
+// 2 tree/src/bin Dir 4096
+// 3 tree/src/bin/tool.rs File 1500
+// 3 tree/src/bin/up Symlink 2
+// 2 tree/src/docs Symlink 7
+// 2 tree/src/lib.rs File 500
+// 2 tree/src/main.rs File 2000
+
+/* Following the links, 'src/docs' becomes a directory, and the walk goes into it. 'src/bin/up' would take it back into 'src', which it is already inside, so that's reported as an error and skipped. The walk carries on regardless. */
+
+show(walk("tree").sort(Sort::Name).follow_links(true));
+// 1 tree/Cargo.toml File 10
+// 1 tree/docs Dir 4096
+// 2 tree/docs/broken Symlink 7
+// 2 tree/docs/guide.md File 3000
+// 2 tree/docs/notes.txt File 2
+// 1 tree/src Dir 4096
+// 2 tree/src/bin Dir 4096
+// 3 tree/src/bin/tool.rs File 1500
+// error: tree/src/bin/up: symlink loop back to /tmp/tree/src
+// 2 tree/src/docs Dir 4096
+// 3 tree/src/docs/broken Symlink 7
+// 3 tree/src/docs/guide.md File 3000
+// 3 tree/src/docs/notes.txt File 2
+// 2 tree/src/lib.rs File 500
+// 2 tree/src/main.rs File 2000
+
+show(walk("tree").sort(Sort::Size).filter(extension("rs")).filter(size(1025..)));
+// 3 tree/src/bin/tool.rs File 1500
+// 2 tree/src/main.rs File 2000
+
+/* Notice that 'tool.rs' comes before the bigger 'main.rs'. They are in different directories, and the biggest thing in 'src' is the directory 'bin', so it comes first, along with everything inside it. */
+
+let docs: Vec<Filter> = vec![Box::new(glob("*.t?t")), Box::new(glob("Cargo.*"))];
+show(walk("tree").sort(Sort::Name).filter(any(docs)));
+// 1 tree/Cargo.toml File 10
+// 2 tree/docs/notes.txt File 2
+
+show(walk("tree").sort(Sort::Name).filter(kind(Kind::File)).filter(not(extension("rs"))));
+// 1 tree/Cargo.toml File 10
+// 2 tree/docs/guide.md File 3000
+// 2 tree/docs/notes.txt File 2
+
+// Files changed in the last minute, newest first
+let since = SystemTime::now() - Duration::from_secs(60);
+fs::write("tree/docs/notes.txt", "changed")?;
+show(walk("tree").sort(Sort::Modified).filter(modified_since(since)).filter(kind(Kind::File)).max_depth(2));
+// 1 tree/Cargo.toml File 10
+// 2 tree/docs/notes.txt File 7
+// 2 tree/docs/guide.md File 3000
+// 2 tree/src/lib.rs File 500
+// 2 tree/src/main.rs File 2000
+
+show(walk("nonesuch"));
+// error: nonesuch: No such file or directory (os error 2)
+
+/* A directory we aren't allowed to read comes out like any other, followed by the error. (This has to be tried as an ordinary user; 'root' can read anything.) */
+
+// $ mkdir -p private/open private/secret
+// $ chmod 000 private/secret
+// (and a file in each)
+
+show(walk("private").sort(Sort::Name));
+// 1 private/open Dir 4096
+// 2 private/open/a.txt File 3
+// 1 private/secret Dir 4096
+// error: private/secret: Permission denied (os error 13)
@@ -0,0 +1,236 @@
+// run.rs
+
+/* 'shell' in process3.rs is handy, but it throws a lot away. It adds '2>&1' so that errors come out mixed in with the output, and there's no way to tell them apart again. It only says whether the command worked, not what the exit code was, or whether it was killed. If there's no shell it panics, and if the command never finishes then neither does 'shell'. So let's write 'run', which runs any 'Command' and tells us everything, with an optional time limit and optional input.
+
+How a process finished is either an exit code, or on Unix a signal which killed it (then there's no exit code, which is why 'ExitStatus::code' returns an 'Option'). The signal number comes from a Unix-only extension trait, as with the permission bits in file10.rs, so that part is only compiled on Unix. Converting with 'From' means we can just say 'status.into()'. */
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How a process finished
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Exit {
+    Code(i32),
+    Signal(i32)
+}
+
+impl From<ExitStatus> for Exit {
+    fn from(status: ExitStatus) -> Exit {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(sig) = status.signal() {
+                return Exit::Signal(sig);
+            }
+        }
+        // only a signal leaves no exit code
+        Exit::Code(status.code().unwrap_or(-1))
+    }
+}
+
+/* The output keeps the two streams apart, and says how long the command took. */
+
+#[derive(Debug, Clone)]
+struct CommandOutput {
+    stdout: String,
+    stderr: String,
+    exit: Exit,
+    duration: Duration
+}
+
+impl CommandOutput {
+    fn success(&self) -> bool {
+        self.exit == Exit::Code(0)
+    }
+}
+
+/* Instead of panicking, 'run' returns an error, and it's worth having our own type for it, as in error1.rs, since the caller may well want to tell the cases apart. A program which isn't there is the most common problem, so it gets its own variant. Every error says which program it is about. 'source' gives the underlying 'io::Error', so that code which follows the chain of causes can find it. */
+
+#[derive(Debug)]
+enum RunError {
+    NotFound(String),
+    Io(String, io::Error),
+    Timeout(String, Duration)
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RunError::NotFound(ref prog) => write!(f, "{}: command not found", prog),
+            RunError::Io(ref prog, ref e) => write!(f, "{}: {}", prog, e),
+            RunError::Timeout(ref prog, d) => write!(f, "{}: timed out after {:?}", prog, d)
+        }
+    }
+}
+
+impl Error for RunError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            RunError::Io(_, ref e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+/* Now the tricky part. A pipe only holds so much (64K on Linux), and then the process writing to it has to wait until someone reads. If we wrote all the input and then read the output, a program like 'cat' would fill its output pipe while we were still writing, and stop reading its input, and the two processes would wait for each other forever. 'wait_with_output' in process4.rs avoids this for the output, but it can't send input or stop after a while. So each pipe gets a thread of its own, and they all go at once.
+
+There's no 'wait' with a time limit in the standard library, but 'try_wait' asks whether the child has finished without waiting. So we ask every 10ms until it has finished or the time is up. Then we 'kill' it, and 'wait' for it anyway, since a dead child which nobody has waited for stays around as a "zombie".
+
+After a timeout we don't wait for the threads. And the program finishing isn't the end of the story: if the command was run by a shell, anything the shell started in the background may still be running and holding the pipes open, so the reading threads don't see the end of the output until it exits too. 'sleep 30 & echo hi' finishes at once, but its output doesn't end for thirty seconds. So the time limit covers the threads as well. Each thread sends its result down a channel, as in thread6.rs, and 'recv_timeout' waits for it only as long as there is time left. If the time runs out, the threads are left to finish by themselves whenever the pipes close. Input which the program didn't read isn't an error either: 'true' never reads anything, and the writer gets a "broken pipe" when it exits. */
+
+// Run 'f' in a thread of its own, and get its result from a channel
+fn in_thread<T,F>(f: F) -> mpsc::Receiver<T>
+where T: Send + 'static, F: FnOnce() -> T + Send + 'static {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(f());
+    });
+    rx
+}
+
+// Read everything from a pipe in a thread of its own
+fn collect<R: Read + Send + 'static>(pipe: Option<R>) -> mpsc::Receiver<io::Result<Vec<u8>>> {
+    in_thread(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_end(&mut buf)?;
+        }
+        Ok(buf)
+    })
+}
+
+// Wait for a thread's result, but not past the deadline
+fn receive<T>(rx: &mpsc::Receiver<T>, deadline: Option<Instant>) -> Option<T> {
+    match deadline {
+        None => rx.recv().ok(),
+        Some(deadline) => rx.recv_timeout(deadline.saturating_duration_since(Instant::now())).ok()
+    }
+}
+
+fn run(cmd: &mut Command, input: Option<&[u8]>, timeout: Option<Duration>) -> Result<CommandOutput,RunError> {
+    let prog = cmd.get_program().to_string_lossy().into_owned();
+    let io_err = |e: io::Error| RunError::Io(prog.clone(), e);
+    let start = Instant::now();
+    let deadline = timeout.map(|timeout| start + timeout);
+    let timed_out = || RunError::Timeout(prog.clone(), timeout.unwrap_or_default());
+    let mut child = cmd
+        .stdin(if input.is_some() {Stdio::piped()} else {Stdio::null()})
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| if e.kind() == io::ErrorKind::NotFound {RunError::NotFound(prog.clone())} else {io_err(e)})?;
+
+    // all three pipes at once, so that none of them can fill up and stop the others
+    let writer = input.map(|input| {
+        let input = input.to_vec();
+        let mut stdin = child.stdin.take().unwrap();
+        in_thread(move || stdin.write_all(&input))
+    });
+    let stdout = collect(child.stdout.take());
+    let stderr = collect(child.stderr.take());
+
+    let status = match deadline {
+        None => child.wait().map_err(io_err)?,
+        Some(deadline) => loop {
+            if let Some(status) = child.try_wait().map_err(io_err)? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                // kill it, and wait so that it doesn't become a zombie
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(timed_out());
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    };
+    let duration = start.elapsed();
+
+    // the pipes may stay open after the program has finished, so the deadline still applies
+    if let Some(writer) = writer {
+        match receive(&writer, deadline).ok_or_else(timed_out)? {
+            // a program doesn't have to read all its input
+            Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => (),
+            res => res.map_err(io_err)?
+        }
+    }
+    let stdout = receive(&stdout, deadline).ok_or_else(timed_out)?.map_err(io_err)?;
+    let stderr = receive(&stderr, deadline).ok_or_else(timed_out)?.map_err(io_err)?;
+    Ok(CommandOutput{
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        exit: status.into(),
+        duration: duration
+    })
+}
+
+/* 'shell' is now a thin layer over 'run', and 'shell_success' can ignore the details it doesn't care about. 'Option::filter' keeps the value only if it passes the test. */
+
+fn shell(cmd: &str, timeout: Option<Duration>) -> Result<CommandOutput,RunError> {
+    let (shell, flag) = if cfg!(windows) {("cmd.exe", "/c")} else {("/bin/sh", "-c")};
+    run(Command::new(shell).arg(flag).arg(cmd), None, timeout)
+}
+
+fn shell_success(cmd: &str) -> Option<String> {
+    shell(cmd, None).ok()
+        .filter(|out| out.success())
+        .map(|out| out.stdout.trim_end().to_string())
+}
+
+let out = shell("echo hello; echo oops >&2; exit 3", None)?;
+println!("{:?} {:?} {:?} {}", out.stdout, out.stderr, out.exit, out.success());
+// "hello\n" "oops\n" Code(3) false
+
+println!("{:?}", shell_success("which sh"));
+println!("{:?}", shell_success("false"));
+// Some("/usr/bin/sh")
+// None
+
+// Standard input, without a shell
+let out = run(Command::new("sort").arg("-r"), Some(b"pear\napple\norange\n"), None)?;
+println!("{:?} {:?}", out.stdout, out.exit);
+// "pear\norange\napple\n" Code(0)
+
+// A process which kills itself
+let out = shell("kill -TERM $$", None)?;
+println!("{:?}", out.exit);
+// Signal(15)
+
+/* The errors. Note that 'sleep 1; echo never' stops after 200ms too, even though the shell had started 'sleep', which lives on for another second. */
+
+let res = run(Command::new("sleep").arg("5"), None, Some(Duration::from_millis(200)));
+if let Err(e) = res {
+    println!("error: {}", e);
+}
+// error: sleep: timed out after 200ms
+
+let start = Instant::now();
+let res = shell("sleep 1; echo never", Some(Duration::from_millis(200)));
+println!("{} {:?}", res.unwrap_err(), start.elapsed());
+// /bin/sh: timed out after 200ms 201.733387ms
+
+// The shell finishes at once, but the background 'sleep' keeps its output open
+let start = Instant::now();
+let res = shell("sleep 30 & echo hi", Some(Duration::from_millis(200)));
+println!("{} {:?}", res.unwrap_err(), start.elapsed());
+// /bin/sh: timed out after 200ms 200.099907ms
+
+let e = run(&mut Command::new("no-such-program"), None, None).unwrap_err();
+println!("{}", e);
+// no-such-program: command not found
+
+let e = run(&mut Command::new("/etc/passwd"), None, None).unwrap_err();
+println!("{}", e);
+// /etc/passwd: Permission denied (os error 13)
+
+/* And to check that big input and output don't get stuck, a megabyte or so through 'cat': */
+
+let big: Vec<u8> = (0..200000).flat_map(|i| format!("{}\n", i).into_bytes()).collect();
+let out = run(&mut Command::new("cat"), Some(&big), Some(Duration::from_secs(10)))?;
+assert_eq!(out.stdout.as_bytes(), &big[..]);
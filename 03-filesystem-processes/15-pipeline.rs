@@ -0,0 +1,210 @@
+// pipeline.rs
+
+/* The shell is very good at joining programs together: 'grep foo file | sort | uniq -c' runs three programs at once, with each one's output going straight into the next one's input. It's tempting to get the same effect with 'shell' from process3.rs, but then the command is a string which the shell will take apart again. A file name with a space in it becomes two arguments, and a '$' or a ';' or a backquote in an argument will do something we didn't want, which is how a lot of security holes happen. 'Command' doesn't have this problem, since each argument is passed to the program exactly as it is. All we need is a way to join 'Command's together.
+
+A 'Pipeline' is a list of commands, along with where the first one reads from and where the last one writes to. By default the first stage reads our own standard input, as the shell does, and the output of the last stage is captured. */
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+
+enum Output {
+    Capture,
+    // the file, and whether to append to it
+    File(PathBuf, bool)
+}
+
+struct Pipeline {
+    commands: Vec<Command>,
+    input: Option<PathBuf>,
+    output: Output
+}
+
+/* 'cmd' is a shortcut for the common case of a program and its arguments. A 'Command' which needs more setting up, such as 'current_dir' or 'env', can be passed to 'command' instead. As with 'Walk' in walk.rs, each method takes the pipeline and returns it, so that they can be chained. */
+
+fn with_name(name: &str, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", name, e))
+}
+
+impl Pipeline {
+    fn new() -> Pipeline {
+        Pipeline{commands: Vec::new(), input: None, output: Output::Capture}
+    }
+
+    fn cmd(self, program: &str, args: &[&str]) -> Pipeline {
+        let mut command = Command::new(program);
+        command.args(args);
+        self.command(command)
+    }
+
+    fn command(mut self, command: Command) -> Pipeline {
+        self.commands.push(command);
+        self
+    }
+
+    fn stdin_file<P: AsRef<Path>>(mut self, path: P) -> Pipeline {
+        self.input = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    fn stdout_file<P: AsRef<Path>>(mut self, path: P) -> Pipeline {
+        self.output = Output::File(path.as_ref().to_path_buf(), false);
+        self
+    }
+
+    fn append_file<P: AsRef<Path>>(mut self, path: P) -> Pipeline {
+        self.output = Output::File(path.as_ref().to_path_buf(), true);
+        self
+    }
+    ...
+}
+
+/* The result holds the captured output and the status of every stage. The shell normally only reports the status of the last stage, so 'grep foo nofile | sort' counts as working, since 'sort' was perfectly happy sorting nothing. 'set -o pipefail' fixes that: a pipeline fails if any stage fails, and its status is that of the last stage which failed. We keep all the statuses, and 'success' and 'status' work the 'pipefail' way. */
+
+#[derive(Debug)]
+struct PipelineOutput {
+    stdout: String,
+    statuses: Vec<ExitStatus>
+}
+
+impl PipelineOutput {
+    // Like 'set -o pipefail': the pipeline fails if any stage does
+    fn success(&self) -> bool {
+        self.statuses.iter().all(|s| s.success())
+    }
+
+    // The status of the last stage which failed, or of the last stage if none did
+    fn status(&self) -> ExitStatus {
+        *self.statuses.iter().rev().find(|s| ! s.success()).unwrap_or(self.statuses.last().unwrap())
+    }
+}
+
+/* Now to connect the stages. When a 'Command' has 'Stdio::piped()' for its output, the 'Child' gets a 'ChildStdout', the reading end of the pipe, and a 'ChildStdout' can be turned into a 'Stdio' for the next command's input. So the pipes run straight from one child to the next, and the data never passes through our program. A 'File' can be turned into a 'Stdio' too, which is how the redirections work.
+
+One detail matters a lot. A 'Command' holds on to the 'Stdio's it has been given, even after 'spawn', and while anyone holds the reading end of a pipe, the writer doesn't know the reader has gone. In 'yes | head -n 3', 'yes' only stops because it finds that nobody is reading any more, once 'head' has finished; if we still held a copy of the pipe, 'yes' would wait forever. That's why each 'Command' is dropped as soon as its child has started, and why 'run' takes 'self' by value and moves the commands out with 'into_iter'.
+
+If a stage can't be started, the stages which were already running are killed, so that nothing is left behind. The same goes for an error while reading the output or waiting for a stage; returning straight away with '?' would leave the other stages running, with nobody to wait for them. Reading the captured output before waiting for the children also matters: the last stage might fill its pipe and stop, waiting for us to read, while we were waiting for it to finish. */
+
+fn run(self) -> io::Result<PipelineOutput> {
+    if self.commands.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty pipeline"));
+    }
+    let mut stdin = match self.input {
+        Some(ref path) => Stdio::from(File::open(path).map_err(|e| with_name(&path.display().to_string(), e))?),
+        None => Stdio::inherit()
+    };
+    let last = self.commands.len() - 1;
+    let mut children: Vec<Child> = Vec::new();
+    for (i, mut command) in self.commands.into_iter().enumerate() {
+        let stdout = if i < last {
+            Stdio::piped()
+        } else {
+            match self.output {
+                Output::Capture => Stdio::piped(),
+                Output::File(ref path, append) => {
+                    let file = OpenOptions::new().write(true).create(true).append(append).truncate(! append).open(path);
+                    match file {
+                        Ok(file) => Stdio::from(file),
+                        Err(e) => {
+                            kill_all(&mut children);
+                            return Err(with_name(&path.display().to_string(), e));
+                        }
+                    }
+                }
+            }
+        };
+        let name = command.get_program().to_string_lossy().into_owned();
+        let child = command.stdin(stdin).stdout(stdout).spawn();
+        // the command still holds its end of the pipe, which has to be closed
+        drop(command);
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                kill_all(&mut children);
+                return Err(with_name(&name, e));
+            }
+        };
+        // this stage's output is the next stage's input
+        stdin = if i < last {Stdio::from(child.stdout.take().unwrap())} else {Stdio::null()};
+        children.push(child);
+    }
+
+    let mut stdout = String::new();
+    if let Some(mut out) = children[last].stdout.take() {
+        let mut bytes = Vec::new();
+        if let Err(e) = out.read_to_end(&mut bytes) {
+            kill_all(&mut children);
+            return Err(e);
+        }
+        stdout = String::from_utf8_lossy(&bytes).into_owned();
+    }
+    let mut statuses = Vec::new();
+    for i in 0..children.len() {
+        match children[i].wait() {
+            Ok(status) => statuses.push(status),
+            Err(e) => {
+                // don't leave the later stages running
+                kill_all(&mut children);
+                return Err(e);
+            }
+        }
+    }
+    Ok(PipelineOutput{stdout: stdout, statuses: statuses})
+}
+
+fn kill_all(children: &mut Vec<Child>) {
+    for child in children {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/* Here's the pipeline from the start, with a file name which would need quoting in the shell. */
+
+let out = Pipeline::new()
+    .cmd("grep", &["foo", "fruit list.txt"])
+    .cmd("sort", &[])
+    .cmd("uniq", &["-c"])
+    .run()?;
+print!("{}", out.stdout);
+// just the exit codes
+let codes: Vec<Option<i32>> = out.statuses.iter().map(|s| s.code()).collect();
+println!("{:?} {}", codes, out.success());
+//       3 foo bar
+//       2 food
+// [Some(0), Some(0), Some(0)] true
+
+/* 'grep' exits with 1 when it finds nothing. The shell would call this pipeline a success, because 'sort' worked; with 'pipefail', it isn't. */
+
+let out = Pipeline::new().cmd("grep", &["nothing", "fruit list.txt"]).cmd("sort", &[]).run()?;
+println!("{:?} {} {:?}", out.stdout, out.success(), out.status().code());
+// "" false Some(1)
+
+/* 'yes' doesn't exit at all; it is killed by the 'SIGPIPE' signal when 'head' stops reading, so it has no exit code. 'pipefail' in 'bash' counts that as a failure too. */
+
+let out = Pipeline::new().cmd("yes", &[]).cmd("head", &["-n", "3"]).run()?;
+let codes: Vec<Option<i32>> = out.statuses.iter().map(|s| s.code()).collect();
+println!("{:?} {:?} {}", out.stdout, codes, out.success());
+// "y\ny\ny\n" [None, Some(0)] false
+
+/* Redirection: 'sort -r < "fruit list.txt" | head -n 2 > top.txt', and then appending with '>>'. Nothing is captured when the output goes to a file. The '$HOME' and the rest are just characters in an argument to 'echo'. */
+
+let out = Pipeline::new()
+    .stdin_file("fruit list.txt")
+    .cmd("sort", &["-r"])
+    .cmd("head", &["-n", "2"])
+    .stdout_file("top.txt")
+    .run()?;
+println!("{:?} {:?}", out.stdout, fs::read_to_string("top.txt")?);
+// "" "food\nfood\n"
+
+Pipeline::new().cmd("echo", &["$HOME; rm -rf / `date`"]).append_file("top.txt").run()?;
+println!("{:?}", fs::read_to_string("top.txt")?);
+// "food\nfood\n$HOME; rm -rf / `date`\n"
+
+println!("{:?}", Pipeline::new().cmd("sort", &[]).cmd("nosuch", &[]).run().err().map(|e| e.to_string()));
+println!("{:?}", Pipeline::new().stdin_file("nofile").cmd("sort", &[]).run().err().map(|e| e.to_string()));
+// Some("nosuch: No such file or directory (os error 2)")
+// Some("nofile: No such file or directory (os error 2)")
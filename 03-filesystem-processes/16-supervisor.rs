@@ -0,0 +1,374 @@
+// supervisor.rs
+
+/* In process4.rs we started 'rustc' and waited for it to finish. A server is different: its helper programs are meant to run for as long as it does, and if one of them crashes, somebody has to notice and start it again. That somebody is a supervisor, like 'systemd' or 'supervisord', and with 'spawn', 'try_wait' and a thread we can write a small one.
+
+Each child has a name, a program with its arguments, and a policy for what to do when it stops. 'Never' leaves it stopped; 'OnFailure' starts it again only if it failed; 'Always' starts it again whatever happened. Restarting straight away is fine for a program which crashes once in a blue moon, but one which fails as soon as it starts (say, because its configuration file is broken) would be restarted hundreds of times a second. 'Backoff' waits before each restart, twice as long each time but never more than 'max_delay', and gives up after 'max_retries' failures in a row. */
+
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Restart {
+    Never,
+    OnFailure,
+    Always,
+    // on failure, waiting twice as long each time up to 'max_delay', and giving up after 'max_retries'
+    Backoff{first: Duration, max_delay: Duration, max_retries: u32}
+}
+
+/* A 'Command' can't be cloned, and we need to run the same program again and again, so a 'Spec' keeps the program and arguments and makes a new 'Command' for every start. */
+
+#[derive(Debug, Clone)]
+struct Spec {
+    program: String,
+    args: Vec<String>,
+    restart: Restart
+}
+
+impl Spec {
+    fn new(program: &str, args: &[&str]) -> Spec {
+        Spec{program: program.to_string(), args: args.iter().map(|s| s.to_string()).collect(), restart: Restart::Never}
+    }
+
+    fn restart(mut self, restart: Restart) -> Spec {
+        self.restart = restart;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum State {
+    // running, with this process id
+    Running(u32),
+    // waiting this long to start again
+    Restarting(Duration),
+    // stopped for good, with the exit code if there was one
+    Exited(Option<i32>),
+    // failed this many times, and won't be started again
+    GaveUp(u32)
+}
+
+struct Process {
+    name: String,
+    spec: Spec,
+    log: PathBuf,
+    child: Option<Child>,
+    started: Instant,
+    failures: u32,
+    restart_at: Option<Instant>,
+    state: State
+}
+
+// a process which runs this long is working again, and its failures are forgotten
+const STABLE: Duration = Duration::from_secs(10);
+
+/* Every child gets a log file of its own, named after it. A 'File' can be turned into a 'Stdio', as in pipeline.rs, so the child writes straight into the file and we don't need a thread to copy its output. Standard output and standard error share the file, through 'try_clone', so the log reads in the order things happened. The file is opened for appending, which means that every write goes on the end, even with several writers; so the supervisor can add its own notes about starting and stopping to the same file. (A child can write before the note of its starting arrives, so the two occasionally swap places.) */
+
+impl Process {
+    // Append a line about this process to its log
+    fn note(&self, msg: &str) {
+        if let Ok(mut f) = OpenOptions::new().create(true).append(true).open(&self.log) {
+            let _ = writeln!(f, "[supervisor] {}: {}", self.name, msg);
+        }
+    }
+
+    fn start(&mut self) -> io::Result<()> {
+        let log = OpenOptions::new().create(true).append(true).open(&self.log)?;
+        let child = Command::new(&self.spec.program)
+            .args(&self.spec.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::from(log.try_clone()?))
+            .stderr(Stdio::from(log))
+            .spawn()?;
+        self.note(&format!("started, pid {}", child.id()));
+        self.state = State::Running(child.id());
+        self.started = Instant::now();
+        self.restart_at = None;
+        self.child = Some(child);
+        Ok(())
+    }
+    ...
+}
+
+/* When a child stops, the policy decides what happens next. A restart isn't done here; we just note when it is due, so that the supervisor never sleeps while a backoff runs out and can keep an eye on the other children. Failing to start at all counts as a failure, which is why the status is an 'Option'.
+
+'max_retries' limits failures in a row. A program which runs happily for days and then crashes shouldn't be held to account for a crash last month, so a run which lasted at least 'STABLE' clears the count. That only goes for a real run: if the program couldn't be started at all, 'started' is still the time of the last start which worked, and it says nothing about this one.
+
+Doubling gets big quickly. By the 33rd failure, the multiplier of 2 to the power 32 no longer fits in a 'u32', and in a debug build the overflow would panic inside the watcher thread, while it holds the lock; the 'Mutex' would then be "poisoned", and every later 'lock' would fail. So the sums are done with 'checked_pow' and 'checked_mul', which give 'None' rather than overflowing, and anything too big is just 'max_delay'. */
+
+// Called when the process has stopped, or couldn't be started
+fn stopped(&mut self, status: Option<ExitStatus>) {
+    let failed = status.map(|s| ! s.success()).unwrap_or(true);
+    if status.is_some() && self.started.elapsed() >= STABLE {
+        self.failures = 0;
+    }
+    if failed {
+        self.failures += 1;
+    }
+    let delay = match self.spec.restart {
+        Restart::Never => None,
+        Restart::OnFailure => if failed {Some(Duration::ZERO)} else {None},
+        Restart::Always => Some(Duration::ZERO),
+        Restart::Backoff{first, max_delay, max_retries} => {
+            if ! failed {
+                None
+            } else if self.failures > max_retries {
+                self.note(&format!("failed {} times, giving up", self.failures));
+                self.state = State::GaveUp(self.failures);
+                return;
+            } else {
+                let delay = 2u32.checked_pow(self.failures - 1).and_then(|m| first.checked_mul(m));
+                Some(delay.unwrap_or(max_delay).min(max_delay))
+            }
+        }
+    };
+    match delay {
+        Some(delay) => {
+            self.note(&format!("restarting in {:?}", delay));
+            self.state = State::Restarting(delay);
+            self.restart_at = Some(Instant::now() + delay);
+        },
+        None => self.state = State::Exited(status.and_then(|s| s.code()))
+    }
+}
+
+/* 'check' is what the supervisor does to each child, over and over. 'try_wait' is 'wait' without the waiting: it gives 'None' if the child is still running. Once a child has exited, 'try_wait' has collected its status, so the 'Child' is dropped; otherwise it would hang around as a "zombie" until somebody asked. */
+
+// Look at the process, and restart it if it is time
+fn check(&mut self) {
+    if let Some(ref mut child) = self.child {
+        match child.try_wait() {
+            Ok(None) => return,
+            Ok(Some(status)) => {
+                self.child = None;
+                self.note(&format!("exited with {}", status));
+                self.stopped(Some(status));
+            },
+            Err(e) => {
+                self.note(&format!("cannot wait: {}", e));
+                return;
+            }
+        }
+    }
+    if self.restart_at.map(|t| Instant::now() >= t).unwrap_or(false) {
+        if let Err(e) = self.start() {
+            self.note(&format!("cannot start: {}", e));
+            self.stopped(None);
+        }
+    }
+}
+
+/* Stopping politely matters. The 'kill' method of 'Child' sends 'SIGKILL' on Unix, which can't be caught, so the child gets no chance to finish writing its files or to tell its clients it's going. The polite request is 'SIGTERM'. The standard library has no way to send it, so we ask the 'kill' program to (a crate such as 'nix' can do it directly). Then the child gets a grace period to exit, and only if it is still running after that does it get 'SIGKILL'. Windows has no signals of this kind, so there it is simply killed. A child which is waiting out a backoff has no process to stop, but it isn't going to restart any more, so it counts as exited. */
+
+// Ask the process to stop, then make it stop
+fn stop(&mut self, grace: Duration) {
+    self.restart_at = None;
+    let mut child = match self.child.take() {
+        Some(child) => child,
+        None => {
+            // it won't be restarted now
+            if let State::Restarting(_) = self.state {
+                self.note("stopped while waiting to restart");
+                self.state = State::Exited(None);
+            }
+            return;
+        }
+    };
+    #[cfg(unix)]
+    {
+        let _ = Command::new("kill").arg("-TERM").arg(child.id().to_string()).status();
+        let deadline = Instant::now() + grace;
+        while Instant::now() < deadline {
+            if let Ok(Some(status)) = child.try_wait() {
+                self.note(&format!("stopped with {}", status));
+                self.state = State::Exited(status.code());
+                return;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+    let _ = child.kill();
+    let status = child.wait().ok();
+    self.note("killed");
+    self.state = State::Exited(status.and_then(|s| s.code()));
+}
+
+/* The supervisor itself is a list of processes and a thread which watches them. The list is shared between our thread and the watcher, so it lives in an 'Arc<Mutex<_>>'; whoever holds the lock can look at or change the children. The watcher checks every child twenty times a second. It stops when the 'stopping' flag is set. A flag which is only ever set once doesn't need a 'Mutex', and an 'AtomicBool' is the simplest thing which can be shared safely between threads, like the counter in atomic.rs. */
+
+struct Supervisor {
+    processes: Arc<Mutex<Vec<Process>>>,
+    log_dir: PathBuf,
+    watcher: Option<thread::JoinHandle<()>>,
+    stopping: Arc<AtomicBool>
+}
+
+impl Supervisor {
+    fn new(log_dir: &str) -> io::Result<Supervisor> {
+        fs::create_dir_all(log_dir)?;
+        let processes = Arc::new(Mutex::new(Vec::<Process>::new()));
+        let stopping = Arc::new(AtomicBool::new(false));
+        let watcher = {
+            let processes = processes.clone();
+            let stopping = stopping.clone();
+            thread::spawn(move || {
+                while ! stopping.load(Ordering::SeqCst) {
+                    for p in processes.lock().unwrap().iter_mut() {
+                        p.check();
+                    }
+                    thread::sleep(Duration::from_millis(50));
+                }
+            })
+        };
+        Ok(Supervisor{processes: processes, log_dir: PathBuf::from(log_dir), watcher: Some(watcher), stopping: stopping})
+    }
+
+    fn status(&self) -> Vec<(String,State)> {
+        self.processes.lock().unwrap().iter().map(|p| (p.name.clone(), p.state.clone())).collect()
+    }
+    ...
+}
+
+/* 'spawn' starts a child at once, so that a mistake like a misspelt program name is reported to the caller, rather than turning up later in a log file. Names have to be unique, since they name the log files. */
+
+fn spawn(&self, name: &str, spec: Spec) -> io::Result<()> {
+    let mut processes = self.processes.lock().unwrap();
+    if processes.iter().any(|p| p.name == name) {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{}: already running", name)));
+    }
+    let mut p = Process{
+        name: name.to_string(),
+        spec: spec,
+        log: self.log_dir.join(format!("{}.log", name)),
+        child: None,
+        started: Instant::now(),
+        failures: 0,
+        restart_at: None,
+        state: State::Exited(None)
+    };
+    p.start().map_err(|e| io::Error::new(e.kind(), format!("{}: {}", name, e)))?;
+    processes.push(p);
+    Ok(())
+}
+
+/* Shutting down happens in a careful order. The watcher is stopped first, and we wait for it with 'join'; otherwise it might restart a child we had just stopped. Then each child is stopped in turn. If the supervisor is dropped without a 'shutdown', 'drop' does it, so that no children are left running behind our backs. */
+
+fn shutdown(&mut self, grace: Duration) -> Vec<(String,State)> {
+    self.stopping.store(true, Ordering::SeqCst);
+    if let Some(watcher) = self.watcher.take() {
+        let _ = watcher.join();
+    }
+    for p in self.processes.lock().unwrap().iter_mut() {
+        p.stop(grace);
+    }
+    self.status()
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        if self.watcher.is_some() {
+            self.shutdown(Duration::from_secs(1));
+        }
+    }
+}
+
+/* Here is a little collection of children, using 'sh -c' to make programs which behave in different ways: one which runs until it's stopped, one which finishes at once, one which always fails, one which exits after a while and is restarted, and one which ignores 'SIGTERM'. */
+
+let mut sup = Supervisor::new("logs")?;
+sup.spawn("ticker", Spec::new("sh", &["-c", "while true; do echo tick; sleep 0.2; done"]))?;
+sup.spawn("once", Spec::new("sh", &["-c", "echo done once"]).restart(Restart::OnFailure))?;
+sup.spawn("flaky", Spec::new("sh", &["-c", "echo trying; echo oops >&2; exit 1"])
+    .restart(Restart::Backoff{first: Duration::from_millis(100), max_delay: Duration::from_secs(10), max_retries: 3}))?;
+sup.spawn("short", Spec::new("sh", &["-c", "echo hi; sleep 0.3"]).restart(Restart::Always))?;
+sup.spawn("stubborn", Spec::new("sh", &["-c", "trap '' TERM; echo ignoring TERM; while true; do sleep 0.1; done"]))?;
+println!("{:?}", sup.spawn("ticker", Spec::new("true", &[])).map_err(|e| e.to_string()));
+println!("{:?}", sup.spawn("missing", Spec::new("no-such-program", &[])).map_err(|e| e.to_string()));
+// Err("ticker: already running")
+// Err("missing: No such file or directory (os error 2)")
+
+thread::sleep(Duration::from_millis(300));
+for s in sup.status() {
+    println!("{:?}", s);
+}
+// ("ticker", Running(13432))
+// ("once", Exited(Some(0)))
+// ("flaky", Restarting(200ms))
+// ("short", Running(13436))
+// ("stubborn", Running(13437))
+
+/* 'once' succeeded, so 'OnFailure' left it alone. 'flaky' has failed twice, and is waiting 200ms before its third try. A second and a half later it has run out of retries: it started once and was restarted three times, after 100, 200 and 400ms. 'short' has a new process id, since it has been restarted several times. */
+
+thread::sleep(Duration::from_millis(1500));
+for s in sup.status() {
+    println!("{:?}", s);
+}
+// ("ticker", Running(13432))
+// ("once", Exited(Some(0)))
+// ("flaky", GaveUp(4))
+// ("short", Running(13476))
+// ("stubborn", Running(13437))
+
+let start = Instant::now();
+for s in sup.shutdown(Duration::from_millis(500)) {
+    println!("{:?}", s);
+}
+println!("{:?}", start.elapsed());
+// ("ticker", Exited(None))
+// ("once", Exited(Some(0)))
+// ("flaky", GaveUp(4))
+// ("short", Exited(None))
+// ("stubborn", Exited(None))
+// 508.412007ms
+
+/* A process killed by a signal has no exit code, so the stopped children show 'None'. The shutdown took just over the grace period, because 'stubborn' had to wait it out before being killed. The logs tell the whole story: */
+
+print!("{}", fs::read_to_string("logs/flaky.log")?);
+// [supervisor] flaky: started, pid 13170
+// trying
+// oops
+// [supervisor] flaky: exited with exit status: 1
+// [supervisor] flaky: restarting in 100ms
+// [supervisor] flaky: started, pid 13177
+// trying
+// oops
+// [supervisor] flaky: exited with exit status: 1
+// [supervisor] flaky: restarting in 200ms
+// trying
+// oops
+// [supervisor] flaky: started, pid 13184
+// [supervisor] flaky: exited with exit status: 1
+// [supervisor] flaky: restarting in 400ms
+// [supervisor] flaky: started, pid 13194
+// trying
+// oops
+// [supervisor] flaky: exited with exit status: 1
+// [supervisor] flaky: failed 4 times, giving up
+
+print!("{}", fs::read_to_string("logs/stubborn.log")?);
+// ignoring TERM
+// [supervisor] stubborn: started, pid 13172
+// [supervisor] stubborn: killed
+
+/* 'ticker' ends with "stopped with signal: 15 (SIGTERM)": it took the hint. */
+
+/* A child which is waiting to restart when the supervisor shuts down is reported as exited, not as about to restart, since it never will. */
+
+let mut sup = Supervisor::new("logs")?;
+sup.spawn("slow", Spec::new("false", &[])
+    .restart(Restart::Backoff{first: Duration::from_secs(5), max_delay: Duration::from_secs(60), max_retries: 3}))?;
+thread::sleep(Duration::from_millis(200));
+println!("{:?}", sup.status());
+println!("{:?}", sup.shutdown(Duration::from_secs(1)));
+print!("{}", fs::read_to_string("logs/slow.log")?);
+// [("slow", Restarting(5s))]
+// [("slow", Exited(None))]
+// [supervisor] slow: started, pid 18675
+// [supervisor] slow: exited with exit status: 1
+// [supervisor] slow: restarting in 5s
+// [supervisor] slow: stopped while waiting to restart
@@ -0,0 +1,323 @@
+// clihelp.rs
+
+/* Let's try the first of those improvements to the interactive prompt from sets2.rs. At the moment a command is just a name and a closure, so all the prompt can say about a mistake is "no such command", and every closure has to check its own arguments. If each command also comes with a help line and a description of its arguments, then the 'Cli' can do a lot more for us: a 'help' command, checking the number and types of the arguments before the closure is even called, suggesting a command when the name is misspelt, and listing the commands which start with what has been typed so far, which is what tab completion needs.
+
+The closure type is getting long, so it gets a type alias. Each command is now a struct holding the closure and everything we know about it, and the 'Cli' keeps a second map from aliases to the real command names. */
+
+use std::collections::HashMap;
+use std::io;
+
+type CliResult = Result<String,String>;
+
+type Callback<'a,D> = Box<dyn Fn(&mut D,&[&str])->CliResult + 'a>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArgType {
+    Int,
+    Float,
+    Str
+}
+
+#[derive(Debug, Clone)]
+struct Arg {
+    name: String,
+    kind: ArgType,
+    optional: bool
+}
+
+struct Command<'a,D> {
+    help: String,
+    args: Vec<Arg>,
+    aliases: Vec<String>,
+    callback: Callback<'a,D>
+}
+
+struct Cli<'a,D> {
+    data: D,
+    commands: HashMap<String, Command<'a,D>>,
+    aliases: HashMap<String, String>
+}
+
+/* The arguments are described with a little signature string, which reads the same way as the usage line that 'help' will print. "n:int" is a required integer called 'n', "[y:float]" is an optional number, and a name on its own is a string. Optional arguments go at the end. A bad type name is a mistake in the program, not in what the user typed, so it panics. */
+
+// "n:int [name:str]" -> two arguments, the second optional
+fn parse_signature(sig: &str) -> Vec<Arg> {
+    sig.split_whitespace().map(|word| {
+        let optional = word.starts_with('[') && word.ends_with(']');
+        let word = word.trim_matches(|c| c == '[' || c == ']');
+        let (name, kind) = match word.find(':') {
+            Some(i) => (&word[..i], &word[i+1..]),
+            None => (word, "str")
+        };
+        let kind = match kind {
+            "int" => ArgType::Int,
+            "float" => ArgType::Float,
+            "str" => ArgType::Str,
+            _ => panic!("unknown argument type '{}' in '{}'", kind, sig)
+        };
+        Arg{name: name.to_string(), kind: kind, optional: optional}
+    }).collect()
+}
+
+fn usage(name: &str, args: &[Arg]) -> String {
+    let mut s = name.to_string();
+    for a in args {
+        let kind = match a.kind {
+            ArgType::Int => "int",
+            ArgType::Float => "float",
+            ArgType::Str => "str"
+        };
+        if a.optional {
+            s += &format!(" [{}:{}]", a.name, kind);
+        } else {
+            s += &format!(" {}:{}", a.name, kind);
+        }
+    }
+    s
+}
+
+/* Checking the arguments means counting them, and then making sure that each one will parse as the type it claims to be. The closure still gets the strings, but it knows that each 'int' is a whole number, and each 'float' is a number. It doesn't know that the number fits: an 'int' is checked as an 'i64', since that's the biggest type a command is likely to want, and a command which wants something smaller still has to deal with a number which is too big for it. */
+
+fn check_args(name: &str, sig: &[Arg], args: &[&str]) -> Result<(),String> {
+    let min = sig.iter().filter(|a| ! a.optional).count();
+    if args.len() < min || args.len() > sig.len() {
+        let expected = if min == sig.len() {
+            format!("{}", min)
+        } else {
+            format!("{} to {}", min, sig.len())
+        };
+        return Err(format!("'{}' takes {} argument{}, got {} (usage: {})",
+            name, expected, if expected == "1" {""} else {"s"}, args.len(), usage(name, sig)));
+    }
+    for (a, s) in sig.iter().zip(args) {
+        let ok = match a.kind {
+            ArgType::Int => s.parse::<i64>().is_ok(),
+            ArgType::Float => s.parse::<f64>().is_ok(),
+            ArgType::Str => true
+        };
+        if ! ok {
+            return Err(format!("'{}' is not a valid {} for '{}'", s, if a.kind == ArgType::Int {"integer"} else {"number"}, a.name));
+        }
+    }
+    Ok(())
+}
+
+/* To guess what the user meant, we need to know how close two words are. The usual measure is the edit distance: the smallest number of characters which have to be inserted, deleted or changed to turn one word into the other. "shwo" is two changes away from "show". The classic way to compute it fills in a table with a row for each character of 'a', but each row only depends on the one before it, so a single row is enough if we remember the value diagonally above ('diag') before overwriting it. */
+
+// The number of single-character edits needed to turn 'a' into 'b'
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == cb {
+                diag
+            } else {
+                1 + diag.min(above).min(row[j])
+            };
+            diag = above;
+        }
+    }
+    row[b.len()]
+}
+
+assert_eq!(edit_distance("kitten", "sitting"), 3);
+assert_eq!(edit_distance("", "abc"), 3);
+
+/* 'cmd' now takes the signature and the help line as well, just as promised at the end of sets2.rs. Aliases are added separately, since most commands won't have any. */
+
+impl <'a,D: Sized> Cli<'a,D> {
+    fn new(data: D) -> Cli<'a,D> {
+        Cli{data: data, commands: HashMap::new(), aliases: HashMap::new()}
+    }
+
+    fn cmd<F>(&mut self, name: &str, signature: &str, help: &str, callback: F)
+    where F: Fn(&mut D, &[&str])->CliResult + 'a {
+        let command = Command{
+            help: help.to_string(),
+            args: parse_signature(signature),
+            aliases: Vec::new(),
+            callback: Box::new(callback)
+        };
+        self.commands.insert(name.to_string(), command);
+    }
+
+    fn alias(&mut self, alias: &str, name: &str) {
+        self.commands.get_mut(name).expect("no such command").aliases.push(alias.to_string());
+        self.aliases.insert(alias.to_string(), name.to_string());
+    }
+    ...
+}
+
+/* Both suggestions and completion start from the list of every word which can begin a line: the command names, the aliases, and 'help'. Completion is just a filter on that list. It only completes the first word, since after that we don't know what the arguments are going to be.
+
+A suggestion is the closest word, as long as it is close enough; one edit for short words, two for longer ones. Otherwise "frobnicate" would be told that it probably meant "go". If the closest word is an alias, we suggest the real name instead. */
+
+// All the words that can start a line, in order
+fn names(&self) -> Vec<&str> {
+    let mut names: Vec<&str> = self.commands.keys().chain(self.aliases.keys()).map(|s| s.as_str()).collect();
+    names.push("help");
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn complete(&self, line: &str) -> Vec<&str> {
+    if line.contains(char::is_whitespace) {
+        return Vec::new();
+    }
+    self.names().into_iter().filter(|n| n.starts_with(line)).collect()
+}
+
+fn suggest(&self, word: &str) -> Option<&str> {
+    let max = if word.chars().count() <= 3 {1} else {2};
+    self.names().into_iter()
+        .map(|n| (edit_distance(word, n), self.aliases.get(n).map(|s| s.as_str()).unwrap_or(n)))
+        .filter(|&(d, _)| d <= max)
+        .min()
+        .map(|(_, n)| n)
+}
+
+/* 'help' can't be an ordinary command, because the closures only get to see 'D', and 'help' needs to see the commands themselves. So it is built into the 'Cli'. On its own it lists everything, lined up in two columns; the '{:w$}' format pads the usage to the width of the longest one. Given a command name, it gives the details of that command. */
+
+fn help(&self, args: &[&str]) -> CliResult {
+    if let Some(&word) = args.first() {
+        let name = self.aliases.get(word).map(|s| s.as_str()).unwrap_or(word);
+        let c = match self.commands.get(name) {
+            Some(c) => c,
+            None => return Err(format!("no such command '{}'", word))
+        };
+        let mut s = format!("{}\n    {}", usage(name, &c.args), c.help);
+        if ! c.aliases.is_empty() {
+            s += &format!("\n    aliases: {}", c.aliases.join(", "));
+        }
+        return Ok(s);
+    }
+    let mut names: Vec<&String> = self.commands.keys().collect();
+    names.sort();
+    let mut lines: Vec<(String,&str)> = names.iter()
+        .map(|n| (usage(n, &self.commands[*n].args), self.commands[*n].help.as_str()))
+        .collect();
+    lines.push(("help [command]".to_string(), "show all commands, or one command in detail"));
+    let width = lines.iter().map(|l| l.0.len()).max().unwrap_or(0);
+    let lines: Vec<String> = lines.iter().map(|l| format!("{:w$}  {}", l.0, l.1, w = width)).collect();
+    Ok(lines.join("\n"))
+}
+
+/* 'process' looks up aliases first, and checks the arguments before calling the closure; the question mark operator returns early if they are wrong. (A program which really wants its own 'help' command can still have one.) 'go' now prints the result text as it is, since help is several lines long and '{:?}' would show it all on one line with '\n' in it. */
+
+fn process(&mut self, line: &str) -> CliResult {
+    let parts: Vec<_> = line.split_whitespace().collect();
+    if parts.is_empty() {
+        return Ok("".to_string());
+    }
+    let (word, args) = (parts[0], &parts[1..]);
+    let name = self.aliases.get(word).map(|s| s.as_str()).unwrap_or(word);
+    if name == "help" && ! self.commands.contains_key("help") {
+        return self.help(args);
+    }
+    match self.commands.get(name) {
+        Some(c) => {
+            check_args(name, &c.args, args)?;
+            (c.callback)(&mut self.data, args)
+        },
+        None => match self.suggest(word) {
+            Some(s) => Err(format!("no such command '{}'; did you mean '{}'?", word, s)),
+            None => Err(format!("no such command '{}'", word))
+        }
+    }
+}
+
+fn go(&mut self) {
+    let mut buff = String::new();
+    while io::stdin().read_line(&mut buff).expect("error") > 0 {
+        {
+            let line = buff.trim_start();
+            match self.process(line) {
+                Ok(s) => println!("{}", s),
+                Err(e) => println!("error: {}", e)
+            }
+        }
+        buff.clear();
+    }
+}
+
+fn ok<T: ToString>(s: T) -> CliResult {
+    Ok(s.to_string())
+}
+
+/* The 'go' command no longer needs to count its arguments or deal with nonsense like "boo!". But the answer is an 'i32', so it still has to report a number which is too big, rather than 'unwrap' it and bring down the prompt. */
+
+fn main() {
+    println!("Welcome to the Interactive Prompt! ");
+
+    struct Data {
+        answer: i32
+    }
+
+    let mut cli = Cli::new(Data{answer: 42});
+
+    cli.cmd("go", "n:int", "set the answer", |data,args| {
+        data.answer = args[0].parse().map_err(|e| format!("'{}' is not a valid answer ({})", args[0], e))?;
+        ok(data.answer)
+    });
+    cli.alias("g", "go");
+
+    cli.cmd("show", "", "show the answer", |data,_| {
+        ok(data.answer)
+    });
+
+    cli.cmd("add", "x:float [y:float]", "add one or two numbers to the answer", |data,args| {
+        let sum: f64 = args.iter().map(|s| s.parse::<f64>().unwrap()).sum();
+        ok(data.answer as f64 + sum)
+    });
+
+    cli.go();
+}
+
+/* Output:
+
+Welcome to the Interactive Prompt!
+help
+add x:float [y:float]  add one or two numbers to the answer
+go n:int               set the answer
+show                   show the answer
+help [command]         show all commands, or one command in detail
+help g
+go n:int
+    set the answer
+    aliases: g
+g 32
+32
+go
+error: 'go' takes 1 argument, got 0 (usage: go n:int)
+go boo!
+error: 'boo!' is not a valid integer for 'n'
+go 3000000000
+error: '3000000000' is not a valid answer (number too large to fit in target type)
+add 1.5
+33.5
+add
+error: 'add' takes 1 to 2 arguments, got 0 (usage: add x:float [y:float])
+shwo
+error: no such command 'shwo'; did you mean 'show'?
+gp 10
+error: no such command 'gp'; did you mean 'go'?
+hlep
+error: no such command 'hlep'; did you mean 'help'?
+frobnicate
+error: no such command 'frobnicate'
+
+The completion data isn't used by 'go' yet, since reading a line from 'stdin' doesn't know anything about the Tab key. It's easy to check by itself: */
+
+println!("{:?}", cli.complete("s"));
+println!("{:?}", cli.complete("g"));
+println!("{:?}", cli.complete(""));
+println!("{:?}", cli.complete("go "));
+// ["show"]
+// ["g", "go"]
+// ["add", "g", "go", "help", "show"]
+// []
@@ -0,0 +1,284 @@
+// clilines.rs
+
+/* The prompt in clihelp.rs still reads lines with 'io::stdin().read_line'. That's fine for a quick test, but there's no way to fix a typo except by backspacing over it, and no way to get back the command you typed a moment ago. And once a program has a command language, it is natural to want to put a list of commands in a file and run them all, or to feed commands in from a test.
+
+These are all different ways of getting lines, and the 'Cli' shouldn't care which one it is using. So we make that a trait. A line source hands out lines until it runs dry, and is told the result of each one. The default 'report' prints the result just as 'go' did; a source can also decide that the session should stop, which is what a script does when a command fails. */
+
+trait LineSource {
+    // The next line, or 'None' when there are no more
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>>;
+
+    // Told the result of each line; returning 'false' ends the session
+    fn report(&mut self, res: &CliResult) -> bool {
+        match *res {
+            Ok(ref s) => if ! s.is_empty() { println!("{}", s) },
+            Err(ref e) => println!("error: {}", e)
+        }
+        true
+    }
+}
+
+/* The loop which used to be in 'go' becomes 'run', and works with any line source. Only an I/O error ends it early; a command which fails is the line source's business. */
+
+impl <'a,D: Sized> Cli<'a,D> {
+    ...
+    fn run<S: LineSource>(&mut self, source: &mut S) -> io::Result<()> {
+        while let Some(line) = source.read_line("> ")? {
+            let res = self.process(&line);
+            if ! source.report(&res) {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/* For the interactive prompt we use the 'rustyline' crate, as suggested back in sets2.rs. It gives us line editing, and history on the up and down arrows, and can save the history to a file and load it again next time. Add it to 'Cargo.toml':
+
+[dependencies]
+rustyline = "17"
+
+'rustyline' can also do tab completion if we give it a 'Helper'. A helper has to implement four traits, but only 'Completer' has anything to do here; the others have default methods for everything. ('Hinter' needs to be told what type its hints are, even though we don't give any.) The completer is given the line and the cursor position, and returns where the word being completed starts, along with the candidates. This is the same rule as 'Cli::complete': only the command name is completed. The helper can't borrow the 'Cli', since the 'Cli' needs to be borrowed mutably to run commands, so it keeps its own copy of the names. */
+
+use rustyline::{Context, Editor, Helper};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+
+struct Names(Vec<String>);
+
+impl Completer for Names {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context) -> rustyline::Result<(usize, Vec<String>)> {
+        let line = &line[..pos];
+        if line.contains(char::is_whitespace) {
+            return Ok((pos, Vec::new()));
+        }
+        Ok((0, self.0.iter().filter(|n| n.starts_with(line)).cloned().collect()))
+    }
+}
+
+impl Hinter for Names {
+    type Hint = String;
+}
+impl Highlighter for Names {}
+impl Validator for Names {}
+impl Helper for Names {}
+
+/* Ctrl-C in 'rustyline' throws away the line being typed, so we give back an empty line, which does nothing. Ctrl-D at the start of a line means we're done. 'rustyline' has its own error type, and 'io::Error::other' wraps it up as an I/O error.
+
+Blank lines are not worth remembering. The history is saved when the line source is dropped, so it happens however the session ends. There is nothing useful to do if saving fails, apart from saying so; 'drop' can't return an error. */
+
+struct Interactive {
+    editor: Editor<Names,DefaultHistory>,
+    history: String
+}
+
+impl Interactive {
+    fn new<D>(cli: &Cli<D>, history: &str) -> io::Result<Interactive> {
+        let mut editor = Editor::new().map_err(io::Error::other)?;
+        editor.set_helper(Some(Names(cli.names().into_iter().map(|s| s.to_string()).collect())));
+        // there won't be a history file the first time
+        let _ = editor.load_history(history);
+        Ok(Interactive{editor: editor, history: history.to_string()})
+    }
+}
+
+impl LineSource for Interactive {
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        match self.editor.readline(prompt) {
+            Ok(line) => {
+                if ! line.trim().is_empty() {
+                    let _ = self.editor.add_history_entry(line.as_str());
+                }
+                Ok(Some(line))
+            },
+            // Ctrl-C abandons the line, Ctrl-D finishes
+            Err(ReadlineError::Interrupted) => Ok(Some(String::new())),
+            Err(ReadlineError::Eof) => Ok(None),
+            Err(e) => Err(io::Error::other(e))
+        }
+    }
+}
+
+impl Drop for Interactive {
+    fn drop(&mut self) {
+        if let Err(e) = self.editor.save_history(&self.history) {
+            eprintln!("cannot save history to {}: {}", self.history, e);
+        }
+    }
+}
+
+// 'go' keeps the history in the current directory
+fn go(&mut self) {
+    let res = Interactive::new(self, ".cli-history").and_then(|mut source| self.run(&mut source));
+    if let Err(e) = res {
+        println!("error: {}", e);
+    }
+}
+
+/* A script is read a line at a time, like any text file. Blank lines and comments starting with '#' are skipped, and we count lines so that an error can say where it happened, in the usual 'file:line: message' style of compilers. Errors go to standard error, which is what it is for.
+
+By default the first error stops the script, since later commands probably depend on the earlier ones working. With 'keep_going' it carries on to the end, and 'errors' says how many there were. 'Script' works with any 'BufRead', so a script can come from a string as easily as from a file. */
+
+use std::fs::File;
+use std::io::prelude::*;
+
+struct Script<R> {
+    reader: R,
+    name: String,
+    line: usize,
+    keep_going: bool,
+    errors: usize
+}
+
+impl Script<io::BufReader<File>> {
+    fn open(path: &str, keep_going: bool) -> io::Result<Script<io::BufReader<File>>> {
+        let file = File::open(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+        Ok(Script::new(io::BufReader::new(file), path, keep_going))
+    }
+}
+
+impl <R: BufRead> Script<R> {
+    fn new(reader: R, name: &str, keep_going: bool) -> Script<R> {
+        Script{reader: reader, name: name.to_string(), line: 0, keep_going: keep_going, errors: 0}
+    }
+}
+
+impl <R: BufRead> LineSource for Script<R> {
+    fn read_line(&mut self, _prompt: &str) -> io::Result<Option<String>> {
+        let mut buf = String::new();
+        loop {
+            buf.clear();
+            if self.reader.read_line(&mut buf)? == 0 {
+                return Ok(None);
+            }
+            self.line += 1;
+            // skip comments and blank lines
+            let line = buf.trim();
+            if ! line.is_empty() && ! line.starts_with('#') {
+                return Ok(Some(line.to_string()));
+            }
+        }
+    }
+
+    fn report(&mut self, res: &CliResult) -> bool {
+        match *res {
+            Ok(ref s) => {
+                if ! s.is_empty() {
+                    println!("{}", s);
+                }
+                true
+            },
+            Err(ref e) => {
+                eprintln!("{}:{}: {}", self.name, self.line, e);
+                self.errors += 1;
+                self.keep_going
+            }
+        }
+    }
+}
+
+/* The last line source is for tests. It hands out lines from a list, and instead of printing the results it keeps them, so they can be checked afterwards. */
+
+use std::collections::VecDeque;
+
+struct Memory {
+    lines: VecDeque<String>,
+    results: Vec<CliResult>
+}
+
+impl Memory {
+    fn new(lines: &[&str]) -> Memory {
+        Memory{lines: lines.iter().map(|s| s.to_string()).collect(), results: Vec::new()}
+    }
+}
+
+impl LineSource for Memory {
+    fn read_line(&mut self, _prompt: &str) -> io::Result<Option<String>> {
+        Ok(self.lines.pop_front())
+    }
+
+    fn report(&mut self, res: &CliResult) -> bool {
+        self.results.push(res.clone());
+        true
+    }
+}
+
+// With the 'go' and 'show' commands from clihelp.rs
+let mut lines = Memory::new(&["go 10", "shwo", "go 3000000000", "show"]);
+cli.run(&mut lines).unwrap();
+assert_eq!(lines.results, vec![
+    ok(10),
+    Err("no such command 'shwo'; did you mean 'show'?".to_string()),
+    Err("'3000000000' is not a valid answer (number too large to fit in target type)".to_string()),
+    ok(10)
+]);
+
+let script = "# set things up\ngo 1\n\ngo x\nshow\n";
+let mut s = Script::new(script.as_bytes(), "setup.cli", false);
+cli.run(&mut s).unwrap();
+println!("errors {} answer {}", s.errors, cli.data.answer);
+// 1
+// setup.cli:4: 'x' is not a valid integer for 'n'
+// errors 1 answer 1
+
+let mut s = Script::new(script.as_bytes(), "setup.cli", true);
+cli.run(&mut s).unwrap();
+println!("errors {} answer {}", s.errors, cli.data.answer);
+// 1
+// setup.cli:4: 'x' is not a valid integer for 'n'
+// 1
+// errors 1 answer 1
+
+/* The first time, the script stopped at line 4 and 'show' never ran. The second time it kept going.
+
+Now the program can decide what to do from its command line. Given a file name, it runs that as a script, and the exit code says whether it worked, so the program can be used from shell scripts and 'make'. Otherwise it's the interactive prompt, which now has editing, history on the arrow keys which is still there next time, and tab completion of command names. */
+
+fn main() {
+    ...
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first() {
+        Some(path) => {
+            let keep_going = args.get(1).map(|s| s.as_str()) == Some("--keep-going");
+            let res = Script::open(path, keep_going).and_then(|mut script| {
+                cli.run(&mut script)?;
+                Ok(script.errors)
+            });
+            match res {
+                Ok(0) => (),
+                Ok(n) => {
+                    println!("{} error(s)", n);
+                    std::process::exit(1);
+                },
+                Err(e) => {
+                    println!("error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        },
+        None => cli.go()
+    }
+}
+
+/*
+$ cat t.cli
+go 3
+nope
+show
+$ cargo run t.cli
+3
+t.cli:2: no such command 'nope'
+1 error(s)
+$ cargo run t.cli --keep-going
+3
+t.cli:2: no such command 'nope'
+3
+1 error(s)
+$ cargo run nonesuch.cli
+error: nonesuch.cli: No such file or directory (os error 2)
+*/
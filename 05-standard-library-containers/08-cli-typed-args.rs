@@ -0,0 +1,312 @@
+// cliargs.rs
+
+/* Two things are still clumsy about writing commands for the 'Cli'. The closures get their arguments as strings, so each one has to call 'parse' and deal with the error, just like the 'go' command in sets2.rs. And they have to return a 'String', so a command which works out a number turns it into text, and a command which has nothing to say still has to say "". Then 'go' in sets2.rs printed the whole 'Result', which gave us 'Ok("32")'.
+
+Let's fix the results first. A command returns an 'Output', which says what kind of thing it is. A table has a row of headers, and then rows of cells. 'CliResult' keeps its name, so all the code which just passes results around doesn't change. */
+
+use std::fmt;
+use std::str::FromStr;
+use std::any;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Output {
+    Nothing,
+    Text(String),
+    Int(i64),
+    Float(f64),
+    Table(Vec<String>, Vec<Vec<String>>)
+}
+
+type CliResult = Result<Output,String>;
+
+/* Now the formatting lives in one place, the 'Display' implementation for 'Output', and every command gets it for free. The interesting case is the table. First we find the width of each column, counting characters rather than bytes, and whether it only contains numbers. Numbers are easier to compare when their digits line up, so those columns are right-aligned; '{:>w$}' pads on the left to width 'w', and '{:w$}' pads on the right. The headers are always on the left, with a line of dashes under them. */
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Output::Nothing => Ok(()),
+            Output::Text(ref s) => write!(f, "{}", s),
+            Output::Int(n) => write!(f, "{}", n),
+            Output::Float(x) => write!(f, "{}", x),
+            Output::Table(ref headers, ref rows) => {
+                // each column is as wide as its widest cell, and columns of numbers line up on the right
+                let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+                let mut numeric = vec![true; headers.len()];
+                for row in rows {
+                    for (i, cell) in row.iter().enumerate().take(widths.len()) {
+                        widths[i] = widths[i].max(cell.chars().count());
+                        numeric[i] = numeric[i] && cell.parse::<f64>().is_ok();
+                    }
+                }
+                let line = |cells: &[String], align: bool| -> String {
+                    let cells: Vec<String> = cells.iter().zip(&widths).zip(&numeric).map(|((c, &w), &num)|
+                        if num && align {format!("{:>w$}", c, w = w)} else {format!("{:w$}", c, w = w)}
+                    ).collect();
+                    cells.join("  ").trim_end().to_string()
+                };
+                writeln!(f, "{}", line(headers, false))?;
+                let rule: Vec<String> = widths.iter().map(|&w| "-".repeat(w)).collect();
+                write!(f, "{}", rule.join("  "))?;
+                for row in rows {
+                    write!(f, "\n{}", line(row, true))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/* The line sources from clilines.rs print results with '{}', so 'report' only needs to know that 'Nothing' prints nothing at all, not even an empty line. */
+
+trait LineSource {
+    // The next line, or 'None' when there are no more
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>>;
+
+    // Told the result of each line; returning 'false' ends the session
+    fn report(&mut self, res: &CliResult) -> bool {
+        match *res {
+            Ok(Output::Nothing) => (),
+            Ok(ref out) => println!("{}", out),
+            Err(ref e) => println!("error: {}", e)
+        }
+        true
+    }
+}
+
+impl <R: BufRead> LineSource for Script<R> {
+    ...
+    fn report(&mut self, res: &CliResult) -> bool {
+        match *res {
+            Ok(Output::Nothing) => true,
+            Ok(ref out) => {
+                println!("{}", out);
+                true
+            },
+            Err(ref e) => {
+                eprintln!("{}:{}: {}", self.name, self.line, e);
+                self.errors += 1;
+                self.keep_going
+            }
+        }
+    }
+}
+
+// 'help' gives back 'Output::Text', and 'process' gives 'Output::Nothing' for an empty line
+...
+
+/* The 'ok' helper used 'ToString' before. Now it uses 'Into<Output>', so that 'ok(data.answer)' gives 'Output::Int' and 'ok("done")' gives 'Output::Text'. For that we need the 'From' conversions; implementing 'From' gives us 'Into' for free. The unit type '()' is Rust's way of saying "nothing", so 'ok(())' is a command with nothing to say. */
+
+impl From<()> for Output {
+    fn from(_: ()) -> Output {
+        Output::Nothing
+    }
+}
+
+impl From<String> for Output {
+    fn from(s: String) -> Output {
+        Output::Text(s)
+    }
+}
+
+impl <'a> From<&'a str> for Output {
+    fn from(s: &'a str) -> Output {
+        Output::Text(s.to_string())
+    }
+}
+
+impl From<i32> for Output {
+    fn from(n: i32) -> Output {
+        Output::Int(n as i64)
+    }
+}
+
+impl From<i64> for Output {
+    fn from(n: i64) -> Output {
+        Output::Int(n)
+    }
+}
+
+impl From<usize> for Output {
+    fn from(n: usize) -> Output {
+        Output::Int(n as i64)
+    }
+}
+
+impl From<f64> for Output {
+    fn from(x: f64) -> Output {
+        Output::Float(x)
+    }
+}
+
+fn ok<T: Into<Output>>(value: T) -> CliResult {
+    Ok(value.into())
+}
+
+fn table(headers: &[&str], rows: Vec<Vec<String>>) -> CliResult {
+    Ok(Output::Table(headers.iter().map(|h| h.to_string()).collect(), rows))
+}
+
+/* Now for the arguments. What we would like to write is something like this:
+
+    let (n, name): (i32, String) = args.parse_args()?;
+
+That is, 'parse_args' looks at the type it is asked for, checks that there are the right number of arguments, and parses each one as the type in that position of the tuple. This is the same trick as 'str::parse', which works out from the type wanted what to parse, using the 'FromStr' trait. We need our own trait for "things which can be made from a slice of arguments". */
+
+trait FromArgs: Sized {
+    fn from_args(args: &[&str]) -> Result<Self,String>;
+}
+
+/* An error should say which argument was wrong, what it was, and what it should have been. 'FromStr' errors don't know the name of the type; 'std::any::type_name' does, but it gives the full path, like 'alloc::string::String', so we just keep the last part. The error from 'parse' goes in brackets at the end, which means the type's error has to implement 'Display'. */
+
+// The type name without its module path, so 'alloc::string::String' is just 'String'
+fn short_type_name<T>() -> &'static str {
+    let name = any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+fn arg<T>(i: usize, s: &str) -> Result<T,String>
+where T: FromStr, T::Err: fmt::Display {
+    s.parse().map_err(|e| format!("argument {}: '{}' is not a valid {} ({})", i + 1, s, short_type_name::<T>(), e))
+}
+
+fn count_args(args: &[&str], n: usize) -> Result<(),String> {
+    if args.len() == n {
+        Ok(())
+    } else {
+        Err(format!("expected {} argument{}, got {}", n, if n == 1 {""} else {"s"}, args.len()))
+    }
+}
+
+impl FromArgs for () {
+    fn from_args(args: &[&str]) -> Result<(),String> {
+        count_args(args, 0)
+    }
+}
+
+/* Every size of tuple is a different type, so each needs its own 'impl'. They are all the same apart from the number of elements, which is what 'macro_rules!' is for. The macro is given pairs of a type parameter name and its position; '$(...),+' repeats the pattern once for each pair. The positions are also used as numbers in the code, so '[$($i),+].len()' counts the elements, and 'args[$i]' is the argument for each one. Note the trailing comma in '($($t,)+)': a tuple with one element is written '(A,)', since '(A)' is just 'A' in brackets. */
+
+macro_rules! tuple_from_args {
+    ($($t:ident $i:tt),+) => {
+        impl <$($t),+> FromArgs for ($($t,)+)
+        where $($t: FromStr, $t::Err: fmt::Display),+ {
+            fn from_args(args: &[&str]) -> Result<Self,String> {
+                count_args(args, [$($i),+].len())?;
+                Ok(($(arg::<$t>($i, args[$i])?,)+))
+            }
+        }
+    }
+}
+
+tuple_from_args!(A 0);
+tuple_from_args!(A 0, B 1);
+tuple_from_args!(A 0, B 1, C 2);
+tuple_from_args!(A 0, B 1, C 2, D 3);
+
+/* 'FromArgs::from_args(args)' would do, but a method on the argument slice reads better. We can't add methods to '[&str]' directly, because it isn't our type, but we can implement our own trait for it. */
+
+trait ParseArgs {
+    fn parse_args<T: FromArgs>(&self) -> Result<T,String>;
+}
+
+impl ParseArgs for [&str] {
+    fn parse_args<T: FromArgs>(&self) -> Result<T,String> {
+        T::from_args(self)
+    }
+}
+
+assert_eq!(["1", "2.5", "x"][..].parse_args::<(i32, f64, String)>(), Ok((1, 2.5, "x".to_string())));
+println!("{:?}", ["1", "2.5"][..].parse_args::<(i32, f64, String)>());
+println!("{:?}", ["1", "x"][..].parse_args::<(i32, f64)>());
+println!("{:?}", ["300"][..].parse_args::<(u8,)>());
+println!("{:?}", ["yes"][..].parse_args::<(bool,)>());
+// Err("expected 3 arguments, got 2")
+// Err("argument 2: 'x' is not a valid f64 (invalid float literal)")
+// Err("argument 1: '300' is not a valid u8 (number too large to fit in target type)")
+// Err("argument 1: 'yes' is not a valid bool (provided string was not `true` or `false`)")
+
+// Anything which implements 'FromStr' will do
+let addr: (std::net::Ipv4Addr, u16) = ["127.0.0.1", "8000"][..].parse_args().unwrap();
+println!("{:?}", addr);
+// (127.0.0.1, 8000)
+
+/* The signatures from clihelp.rs are still useful. They drive 'help', and they catch wrong argument counts before the command runs, with a usage line. But they only know about 'int', 'float' and 'str'; 'parse_args' knows the real types, so "300" gets past the signature check as an 'int', and is then rejected as a 'u8'.
+
+Here are the commands again. The 'parse_args' errors are 'String', like the errors in 'CliResult', so '?' passes them straight on. 'add' has an optional argument, so it decides which tuple it wants from the number of arguments. 'powers' returns a table; 'checked_pow' gives 'None' instead of overflowing, and 'ok_or' turns that into an error. */
+
+fn main() {
+    ...
+    cli.cmd("go", "n:int", "set the answer", |data,args| {
+        let (n,) = args.parse_args()?;
+        data.answer = n;
+        ok(data.answer)
+    });
+    cli.alias("g", "go");
+
+    cli.cmd("show", "", "show the answer", |data,_| {
+        ok(data.answer)
+    });
+
+    cli.cmd("add", "x:float [y:float]", "add one or two numbers to the answer", |data,args| {
+        let sum = match args.len() {
+            1 => args.parse_args::<(f64,)>()?.0,
+            _ => {
+                let (x, y): (f64, f64) = args.parse_args()?;
+                x + y
+            }
+        };
+        ok(data.answer as f64 + sum)
+    });
+
+    cli.cmd("reset", "", "set the answer back to 42", |data,_| {
+        data.answer = 42;
+        ok(())
+    });
+
+    cli.cmd("powers", "n:int", "a table of powers of the answer", |data,args| {
+        let (n,): (u32,) = args.parse_args()?;
+        let a = data.answer as i64;
+        let mut rows = Vec::new();
+        for i in 1..=n {
+            let p = a.checked_pow(i).ok_or(format!("{} to the power {} is too big", a, i))?;
+            rows.push(vec![i.to_string(), p.to_string(), format!("{:x}", p)]);
+        }
+        table(&["power", "value", "hex"], rows)
+    });
+    ...
+}
+
+/* Output:
+
+> go 32
+32
+> show
+32
+> add 1.5
+33.5
+> powers 3
+power  value  hex
+-----  -----  ----
+    1     32    20
+    2   1024   400
+    3  32768  8000
+> reset
+> go 2147483648
+error: argument 1: '2147483648' is not a valid i32 (number too large to fit in target type)
+> powers -2
+error: argument 1: '-2' is not a valid u32 (invalid digit found in string)
+
+Nothing is printed after 'reset'. The powers of 32 happen to have no letters in hex, so that column counts as numbers too. With 42, the 'hex' column stays on the left, since "2a" isn't a number. (Though "6e4" is! It is 60000 in scientific notation, which is why the alignment is decided for the whole column and not cell by cell.)
+
+The 'Memory' line source keeps the results themselves, so tests can now check what kind of result a command gave, and not just its text: */
+
+let mut lines = Memory::new(&["reset", "powers 2", "add 0.5", "powers 40"]);
+cli.run(&mut lines).unwrap();
+assert_eq!(lines.results, vec![
+    ok(()),
+    table(&["power", "value", "hex"], vec![
+        vec!["1".to_string(), "42".to_string(), "2a".to_string()],
+        vec!["2".to_string(), "1764".to_string(), "6e4".to_string()]
+    ]),
+    ok(42.5),
+    Err("42 to the power 12 is too big".to_string())
+]);
@@ -0,0 +1,330 @@
+// clitokens.rs
+
+/* 'Cli::process' still splits lines with 'split_whitespace', so there is no way to give a command an argument with a space in it. A 'set' command can't set a name to "John Doe"; it gets "\"John" and "Doe\"". Shells solved this problem long ago, and their rules are familiar to most people who would use a prompt like this, so let's follow them:
+
+- words are separated by whitespace
+- inside single quotes, everything is taken literally: 'John Doe'
+- inside double quotes, '$name' is replaced by the value of a variable, and '\"', '\\' and '\$' stand for the character after the backslash
+- outside quotes, a backslash makes the next character ordinary, so '\ ' is a space which doesn't end the word
+- '${name}' can be used when the name is followed by letters, as in '${name}s'
+- '|' separates commands
+
+Quoted and unquoted pieces next to each other make up one word, so 'a"b c"' is the single word "ab c". Unlike a shell, the value of a variable is never split into several words; '$name' is always one argument, even without quotes.
+
+The tokenizer gives a list of tokens, which are either words or pipes. It doesn't know where the variables live, so it is passed a closure which looks them up. The one subtle part is knowing when there is a word. "" is an empty word, and should be an argument, but two spaces in a row shouldn't make one. So the word being built is an 'Option'; 'get_or_insert_with' starts a word when the first piece of it turns up (even an empty piece), and 'take' ends it, leaving 'None'. Taking an 'Option' gives an iterator of zero or one items, so 'extend' adds the word to the tokens only if there is one. */
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Pipe
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn tokenize<F>(line: &str, var: F) -> Result<Vec<Token>,String>
+where F: Fn(&str) -> Option<String> {
+    let mut tokens = Vec::new();
+    // 'None' between words, so that "" can still be an empty word
+    let mut word: Option<String> = None;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                let w = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => w.push(c),
+                        None => return Err("missing closing '".to_string())
+                    }
+                }
+            },
+            '"' => {
+                let w = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) if c == '"' || c == '\\' || c == '$' => w.push(c),
+                            Some(c) => {
+                                w.push('\\');
+                                w.push(c);
+                            },
+                            None => return Err("missing closing \"".to_string())
+                        },
+                        Some('$') => substitute(&mut chars, w, &var)?,
+                        Some(c) => w.push(c),
+                        None => return Err("missing closing \"".to_string())
+                    }
+                }
+            },
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err("nothing to escape at end of line".to_string())
+            },
+            '$' => substitute(&mut chars, word.get_or_insert_with(String::new), &var)?,
+            '|' => {
+                tokens.extend(word.take().map(Token::Word));
+                tokens.push(Token::Pipe);
+            },
+            c if c.is_whitespace() => tokens.extend(word.take().map(Token::Word)),
+            c => word.get_or_insert_with(String::new).push(c)
+        }
+    }
+    tokens.extend(word.take().map(Token::Word));
+    Ok(tokens)
+}
+
+/* Variable names are read straight from the character iterator. Here 'peek' is needed, because the character after a name belongs to whatever comes next, and must not be used up. A variable which doesn't exist is an error, rather than quietly becoming empty as it would in a shell; a misspelt name is much easier to find that way. */
+
+// After a '$', read a variable name, either 'name' or '{name}', and put its value in 'word'
+fn substitute<F>(chars: &mut Chars, word: &mut String, var: &F) -> Result<(),String>
+where F: Fn(&str) -> Option<String> {
+    let mut name = String::new();
+    if chars.peek() == Some(&'{') {
+        chars.next();
+        loop {
+            match chars.next() {
+                Some('}') => break,
+                Some(c) => name.push(c),
+                None => return Err("missing closing }".to_string())
+            }
+        }
+    } else {
+        while let Some(&c) = chars.peek() {
+            if ! (c.is_alphanumeric() || c == '_') {
+                break;
+            }
+            name.push(c);
+            chars.next();
+        }
+        // a '$' on its own is just a dollar sign
+        if name.is_empty() {
+            word.push('$');
+            return Ok(());
+        }
+    }
+    match var(&name) {
+        Some(value) => {
+            word.push_str(&value);
+            Ok(())
+        },
+        None => Err(format!("no such variable '{}'", name))
+    }
+}
+
+// A test variable 'x' which has a space in it
+let t = |line: &str| tokenize(line, |name| if name == "x" {Some("a b".to_string())} else {None});
+println!("{:?}", t(r#"set name "John Doe""#));
+println!("{:?}", t(r#"a'b c'"d e" "" \| |b"#));
+println!("{:?}", t(r#"echo $x "$x" '$x' \$x ${x}y $ "\$x" "\n""#));
+// Ok([Word("set"), Word("name"), Word("John Doe")])
+// Ok([Word("ab cd e"), Word(""), Word("|"), Pipe, Word("b")])
+// Ok([Word("echo"), Word("a b"), Word("a b"), Word("$x"), Word("$x"), Word("a by"), Word("$"), Word("$x"), Word("\\n")])
+
+println!("{:?}", t("echo $y"));
+println!("{:?}", t("echo 'oops"));
+println!("{:?}", t("echo ${x"));
+println!("{:?}", t("echo \\"));
+// Err("no such variable 'y'")
+// Err("missing closing '")
+// Err("missing closing }")
+// Err("nothing to escape at end of line")
+
+/* (Raw strings 'r#"..."#' are handy for testing this, since the tests are full of quotes and backslashes. Note that "\n" inside double quotes stays as a backslash and an 'n'; only the characters which mean something inside double quotes can be escaped there.)
+
+Now where do the variables come from? They belong to the program, so they should live in 'D' along with the rest of its data. But 'Cli' doesn't know what 'D' looks like. We could require 'D' to implement some trait, but then every program would have to implement it, even ones which don't want variables. Instead, we do what 'Cli' already does for commands, and ask for a closure: given the data, it returns the map of variables. The closure type says that the map is borrowed from the data. */
+
+type Vars<'a,D> = Box<dyn Fn(&D) -> &HashMap<String,String> + 'a>;
+
+struct Cli<'a,D> {
+    data: D,
+    commands: HashMap<String, Command<'a,D>>,
+    aliases: HashMap<String, String>,
+    vars: Option<Vars<'a,D>>
+}
+
+impl <'a,D: Sized> Cli<'a,D> {
+    fn new(data: D) -> Cli<'a,D> {
+        Cli{data: data, commands: HashMap::new(), aliases: HashMap::new(), vars: None}
+    }
+
+    fn vars<F>(&mut self, vars: F)
+    where F: Fn(&D) -> &HashMap<String,String> + 'a {
+        self.vars = Some(Box::new(vars));
+    }
+    ...
+}
+
+/* 'process' now tokenizes the line, and splits the words into commands at each '|'. The variables are looked up inside a block, so that the borrow of 'self.data' is over before any command gets to change it.
+
+The commands are run from left to right. Each result, apart from the last, is turned into text with its 'Display' and becomes the last argument of the next command. A command with no output can't be piped, and that is an error rather than something which silently leaves out an argument. The rest of the old 'process' becomes 'run_command', which works on a list of words; the first error anywhere stops the whole line, thanks to '?'.
+
+It would be a pity if 'go 5 | nosuch' set the answer to 5 before finding out that there's no 'nosuch'. So as much of the line as possible is checked before anything runs: every command must be there and must exist, and 'check_command' runs 'check_args' on its arguments. The piped text isn't known yet, so a stand-in takes its place, which only counts as an argument. That can't catch everything. Whether a command has output, and whether that output suits the next command, is only known once it has run; 'reset | go' has already reset the answer by the time it fails. */
+
+fn process(&mut self, line: &str) -> CliResult {
+    let tokens = {
+        let vars = self.vars.as_ref().map(|f| f(&self.data));
+        tokenize(line, |name| vars.and_then(|v| v.get(name).cloned()))?
+    };
+    // split the words into commands at each pipe
+    let mut commands = vec![Vec::new()];
+    for t in tokens {
+        match t {
+            Token::Word(w) => commands.last_mut().unwrap().push(w),
+            Token::Pipe => commands.push(Vec::new())
+        }
+    }
+    if commands.len() == 1 && commands[0].is_empty() {
+        return Ok(Output::Nothing);
+    }
+    // check the whole line before anything runs
+    if commands.iter().any(|words| words.is_empty()) {
+        return Err("missing command before or after '|'".to_string());
+    }
+    for (i, words) in commands.iter().enumerate() {
+        self.check_command(words, i > 0)?;
+    }
+    let mut piped: Option<String> = None;
+    let last = commands.len() - 1;
+    for (i, mut words) in commands.into_iter().enumerate() {
+        words.extend(piped.take());
+        let res = self.run_command(&words)?;
+        if i == last {
+            return Ok(res);
+        }
+        if res == Output::Nothing {
+            return Err(format!("'{}' has no output for '|'", words[0]));
+        }
+        piped = Some(res.to_string());
+    }
+    unreachable!()
+}
+
+fn run_command(&mut self, words: &[String]) -> CliResult {
+    let parts: Vec<&str> = words.iter().map(|s| s.as_str()).collect();
+    let (word, args) = (parts[0], &parts[1..]);
+    let name = self.aliases.get(word).map(|s| s.as_str()).unwrap_or(word);
+    if name == "help" && ! self.commands.contains_key("help") {
+        return self.help(args);
+    }
+    match self.commands.get(name) {
+        Some(c) => {
+            check_args(name, &c.args, args)?;
+            (c.callback)(&mut self.data, args)
+        },
+        None => Err(self.no_such_command(word))
+    }
+}
+
+fn check_command(&self, words: &[String], piped: bool) -> Result<(),String> {
+    let mut parts: Vec<&str> = words.iter().map(|s| s.as_str()).collect();
+    let word = parts[0];
+    let name = self.aliases.get(word).map(|s| s.as_str()).unwrap_or(word);
+    if name == "help" && ! self.commands.contains_key("help") {
+        return Ok(());
+    }
+    let c = self.commands.get(name).ok_or_else(|| self.no_such_command(word))?;
+    if piped {
+        // stand-in for the piped text, which passes whatever check its argument gets
+        parts.push(match c.args.get(parts.len() - 1) {
+            Some(a) if a.kind != ArgType::Str => "0",
+            _ => ""
+        });
+    }
+    check_args(name, &c.args, &parts[1..])
+}
+
+fn no_such_command(&self, word: &str) -> String {
+    match self.suggest(word) {
+        Some(s) => format!("no such command '{}'; did you mean '{}'?", word, s),
+        None => format!("no such command '{}'", word)
+    }
+}
+
+/* The data gets a map of variables, and the program tells the 'Cli' where to find it. The 'set' command is an ordinary command; it's the program which decides that variables can be set, and how. */
+
+fn main() {
+    struct Data {
+        answer: i32,
+        vars: HashMap<String,String>
+    }
+
+    let mut cli = Cli::new(Data{answer: 42, vars: HashMap::new()});
+    cli.vars(|data| &data.vars);
+
+    cli.cmd("set", "name value", "set a variable", |data,args| {
+        data.vars.insert(args[0].to_string(), args[1].to_string());
+        ok(())
+    });
+
+    cli.cmd("vars", "", "show all variables", |data,_| {
+        let mut rows: Vec<Vec<String>> = data.vars.iter().map(|(k, v)| vec![k.clone(), v.clone()]).collect();
+        rows.sort();
+        table(&["name", "value"], rows)
+    });
+
+    cli.cmd("echo", "text", "show some text", |_,args| {
+        ok(args[0])
+    });
+
+    cli.cmd("greet", "name", "say hello", |_,args| {
+        ok(format!("hello {}", args[0]))
+    });
+    ...
+}
+
+/* Output:
+
+> set name "John Doe"
+> greet $name
+hello John Doe
+> echo 'single $name'
+single $name
+> echo "double \"$name\""
+double "John Doe"
+> echo \$name
+$name
+> echo ${name}s
+John Does
+> echo $nobody
+error: no such variable 'nobody'
+> echo "oops
+error: missing closing "
+> add 1 | go
+43
+> show|echo
+43
+> reset | go
+error: 'reset' has no output for '|'
+> | show
+error: missing command before or after '|'
+> go 5 |
+error: missing command before or after '|'
+> go 7
+7
+> go 5 | nosuch
+error: no such command 'nosuch'
+> show | go 1
+error: 'go' takes 1 argument, got 2 (usage: go n:int)
+> add x | go
+error: 'x' is not a valid number for 'x'
+> show
+7
+> reset
+> set n 3
+> powers $n
+power  value  hex
+-----  -----  -----
+    1     42  2a
+    2   1764  6e4
+    3  74088  12168
+> echo $name | greet
+hello John Doe
+> vars
+name  value
+----  --------
+n     3
+name  John Doe
+
+'add 1' gives 43.0, which displays as "43", so 'go' can parse it as an integer. Piped text is a single argument even when it has spaces in it, as with 'greet'. */
@@ -0,0 +1,330 @@
+// wordcount.rs
+
+/* The word count in maps2.rs is a good start, but it has two problems. Splitting on every character which isn't a letter gives an empty "word" whenever two such characters come together, like a comma followed by a space; and it also breaks "don't" into "don" and "t". It only ever prints the top twenty, and counts one text. Let's make it into something reusable: a 'WordCounter' which can count words or runs of words, leave out common words, read many files at once, and write its results in a form other programs can read.
+
+Finding words properly is harder than it looks, so we let a crate do it. The Unicode standard has rules for where words begin and end (Annex 29, "Text Segmentation"), and 'unicode-segmentation' implements them. Its 'unicode_words' method gives only the words, leaving out the spaces and punctuation between them, so there are no empty words. Add it to 'Cargo.toml':
+
+[dependencies]
+unicode-segmentation = "1"
+
+It's a trait method, so the trait has to be brought into scope with 'use', just as 'io::prelude::*' does for 'read_line'. */
+
+use unicode_segmentation::UnicodeSegmentation;
+
+let sample = "The café's “naïve” résumé—isn't it? 3.14 and 2024; e-mail. Straße STRASSE";
+println!("{:?}", sample.unicode_words().collect::<Vec<_>>());
+// ["The", "café's", "naïve", "résumé", "isn't", "it", "3.14", "and", "2024", "e", "mail", "Straße", "STRASSE"]
+
+/* Apostrophes stay inside words, the fancy quotes and the dash are not words, and "3.14" is one word rather than two. Numbers aren't interesting in a word count, so the counter will skip any word without a letter in it.
+
+The counter has some settings, and the counts themselves. The settings are given with methods that can be chained after 'new', as with 'Command' in process1.rs; but where 'Command' borrows itself mutably, these take 'self' by value and return it, so the finished counter can be kept in a 'let'. Everything has a sensible default, so 'Default' can be derived and 'new' only needs to change the n-gram size.
+
+An n-gram is a run of n words in a row, so the bigrams (n = 2) of "it was the best" are "it was", "was the" and "the best". Counting them shows which phrases come up most often. The counter keeps the last few words in 'window', a 'VecDeque' since words are added at the back and dropped from the front. */
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+#[derive(Debug, Clone, Default)]
+struct WordCounter {
+    fold_case: bool,
+    stop_words: HashSet<String>,
+    n: usize,
+    counts: HashMap<String,u64>,
+    // the last n-1 words, for n-grams which carry on from one line to the next
+    window: VecDeque<String>
+}
+
+impl WordCounter {
+    fn new() -> WordCounter {
+        WordCounter{n: 1, ..Default::default()}
+    }
+
+    fn fold_case(mut self, yes: bool) -> WordCounter {
+        self.fold_case = yes;
+        self
+    }
+
+    fn stop_words<I,S>(mut self, words: I) -> WordCounter
+    where I: IntoIterator<Item = S>, S: AsRef<str> {
+        self.stop_words = words.into_iter().map(|w| w.as_ref().to_lowercase()).collect();
+        self
+    }
+
+    fn ngrams(mut self, n: usize) -> WordCounter {
+        assert!(n > 0, "n-grams need at least one word");
+        self.n = n;
+        self
+    }
+
+    // A counter with the same settings and no counts
+    fn fresh(&self) -> WordCounter {
+        WordCounter{fold_case: self.fold_case, stop_words: self.stop_words.clone(), n: self.n, ..Default::default()}
+    }
+    ...
+}
+
+/* Counting works as in maps2.rs, with 'entry'. Case folding here just means 'to_lowercase', which is good enough for most text, although it won't make "Straße" and "STRASSE" the same word; full Unicode case folding would, but the standard library doesn't have it. Stop words (the very common words like "the" and "of" which fill up the top of any count) are matched ignoring case, whether or not the counts are folded.
+
+Stop words are left out before the n-grams are made, so "best of times" becomes the bigram "best times". */
+
+fn add_text(&mut self, text: &str) {
+    for word in text.unicode_words() {
+        // skip numbers
+        if ! word.chars().any(char::is_alphabetic) {
+            continue;
+        }
+        let word = if self.fold_case {word.to_lowercase()} else {word.to_string()};
+        if self.stop_words.contains(&word.to_lowercase()) {
+            continue;
+        }
+        if self.n == 1 {
+            *self.counts.entry(word).or_insert(0) += 1;
+            continue;
+        }
+        self.window.push_back(word);
+        if self.window.len() == self.n {
+            let gram = self.window.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(" ");
+            *self.counts.entry(gram).or_insert(0) += 1;
+            self.window.pop_front();
+        }
+    }
+}
+
+fn len(&self) -> usize {
+    self.counts.len()
+}
+
+fn total(&self) -> u64 {
+    self.counts.values().sum()
+}
+
+fn get(&self, word: &str) -> u64 {
+    self.counts.get(word).cloned().unwrap_or(0)
+}
+
+/* 'top' sorts as before, but when two words have the same count it puts them in alphabetical order. Otherwise the order of ties would depend on the order of the 'HashMap', and would change from one run to the next. 'then' is how 'Ordering' says "if these are equal, compare this instead". */
+
+// The 'n' most common, with ties in alphabetical order
+fn top(&self, n: usize) -> Vec<(&str,u64)> {
+    let mut entries: Vec<(&str,u64)> = self.counts.iter().map(|(w, &c)| (w.as_str(), c)).collect();
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    entries.truncate(n);
+    entries
+}
+
+let text = "It was the best of times, it was the worst of times, it was the age of wisdom, it was the age of foolishness";
+let mut words = WordCounter::new().fold_case(true);
+words.add_text(text);
+println!("{} {} {:?}", words.len(), words.total(), words.top(5));
+// 10 24 [("it", 4), ("of", 4), ("the", 4), ("was", 4), ("age", 2)]
+assert_eq!(words.get(""), 0);
+
+let stop = ["the", "of", "it", "was", "a", "and"];
+let mut words = WordCounter::new().fold_case(true).stop_words(stop);
+words.add_text(text);
+println!("{:?}", words.top(3));
+// [("age", 2), ("times", 2), ("best", 1)]
+
+let mut words = WordCounter::new().fold_case(true).ngrams(2);
+words.add_text("It was the best of times,\nit was the worst of times");
+println!("{:?}", words.top(4));
+// [("it was", 2), ("of times", 2), ("was the", 2), ("best of", 1)]
+
+let mut words = WordCounter::new().ngrams(3).stop_words(stop);
+words.add_text(text);
+println!("{:?}", words.top(2));
+// [("age wisdom age", 1), ("best times worst", 1)]
+
+/* Files are read a line at a time, so a big file doesn't have to fit in memory. A phrase can carry on from one line to the next, which is why the window is kept between calls to 'add_text'; but it mustn't carry on from the end of one file into the start of another, so it is cleared at the end of each. An error says which file it came from, as in clilines.rs. */
+
+fn add_reader<R: BufRead>(&mut self, mut reader: R) -> io::Result<()> {
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        self.add_text(&line);
+        line.clear();
+    }
+    // n-grams don't carry on into the next file
+    self.window.clear();
+    Ok(())
+}
+
+fn add_file(&mut self, path: &Path) -> io::Result<()> {
+    let file = File::open(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    self.add_reader(io::BufReader::new(file))
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
+
+/* Counting many files is a job which splits up nicely. Each thread gets its own counter, with the same settings, and a share of the files, dealt out like cards so that each gets about the same number. Because every thread has its own counter, there's nothing shared and no locking; at the end the counts are simply added together with 'merge'. We use one thread per processor, which 'available_parallelism' tells us, but never more threads than files.
+
+The closure returns an 'io::Result', and 'join' hands it back, so the first file which can't be read becomes the error for the whole job. (Any threads which haven't been joined yet are left to finish by themselves, and their counts are thrown away.) */
+
+fn merge(&mut self, other: WordCounter) {
+    for (word, count) in other.counts {
+        *self.counts.entry(word).or_insert(0) += count;
+    }
+}
+
+fn add_files(&mut self, paths: &[PathBuf]) -> io::Result<()> {
+    let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(4).min(paths.len().max(1));
+    // deal the files out to the threads like cards
+    let handles: Vec<_> = (0..threads).map(|i| {
+        let mut counter = self.fresh();
+        let paths: Vec<PathBuf> = paths.iter().skip(i).step_by(threads).cloned().collect();
+        thread::spawn(move || -> io::Result<WordCounter> {
+            for path in &paths {
+                counter.add_file(path)?;
+            }
+            Ok(counter)
+        })
+    }).collect();
+    for h in handles {
+        self.merge(h.join().expect("counting thread panicked")?);
+    }
+    Ok(())
+}
+
+/* The results can go out as plain text for people, or as CSV or JSON for other programs. We could pull in 'serde' for the JSON, but a list of words and counts is simple enough to write by hand, as long as we get the quoting right. A CSV field with a comma, a quote or a newline in it goes in double quotes, with any quotes inside doubled. A JSON string escapes quotes, backslashes and control characters. Everything is written to any 'Write', so the output can go to standard output, a file, or a 'Vec<u8>' in a test. */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Text,
+    Csv,
+    Json
+}
+
+fn write_top<W: Write>(&self, out: &mut W, n: usize, format: Format) -> io::Result<()> {
+    let top = self.top(n);
+    match format {
+        Format::Text => {
+            let width = top.iter().map(|e| e.0.chars().count()).max().unwrap_or(0);
+            for (word, count) in top {
+                writeln!(out, "{:w$} {}", word, count, w = width)?;
+            }
+        },
+        Format::Csv => {
+            writeln!(out, "word,count")?;
+            for (word, count) in top {
+                writeln!(out, "{},{}", csv_field(word), count)?;
+            }
+        },
+        Format::Json => {
+            writeln!(out, "[")?;
+            for (i, (word, count)) in top.iter().enumerate() {
+                let comma = if i + 1 < top.len() {","} else {""};
+                writeln!(out, "  {{\"word\": {}, \"count\": {}}}{}", json_string(word), count, comma)?;
+            }
+            writeln!(out, "]")?;
+        }
+    }
+    Ok(())
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+    out
+}
+
+assert_eq!(csv_field("don't"), "don't");
+assert_eq!(csv_field("a,b \"c\""), "\"a,b \"\"c\"\"\"");
+assert_eq!(json_string("a,b \"c\""), "\"a,b \\\"c\\\"\"");
+
+/* Finally a 'main' which puts it all together. The options come first, then any number of files. An option which is wrong prints the usage and exits; 'usage' returns '!', the "never" type, so it can be used wherever a value is expected, as in the 'unwrap_or_else' calls. */
+
+fn usage() -> ! {
+    eprintln!("usage: wordcount [--fold] [--stop file] [--ngrams n] [--top n] [--format text|csv|json] files...");
+    std::process::exit(1);
+}
+
+fn main() {
+    let mut counter = WordCounter::new();
+    let (mut top, mut format, mut paths) = (20, Format::Text, Vec::new());
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fold" => counter = counter.fold_case(true),
+            "--stop" => {
+                let path = args.next().unwrap_or_else(|| usage());
+                let text = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+                    eprintln!("{}: {}", path, e);
+                    std::process::exit(1);
+                });
+                counter = counter.stop_words(text.split_whitespace());
+            },
+            "--ngrams" => match args.next().and_then(|s| s.parse().ok()) {
+                Some(n) if n > 0 => counter = counter.ngrams(n),
+                _ => usage()
+            },
+            "--top" => top = args.next().and_then(|s| s.parse().ok()).unwrap_or_else(|| usage()),
+            "--format" => format = match args.next().as_deref() {
+                Some("text") => Format::Text,
+                Some("csv") => Format::Csv,
+                Some("json") => Format::Json,
+                _ => usage()
+            },
+            s if s.starts_with("--") => usage(),
+            _ => paths.push(PathBuf::from(arg))
+        }
+    }
+    if paths.is_empty() {
+        usage();
+    }
+    if let Err(e) = counter.add_files(&paths) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+    let out = io::stdout();
+    counter.write_top(&mut out.lock(), top, format).expect("cannot write output");
+}
+
+/*
+$ cat stop.txt
+the of and a
+$ cargo run -- --fold --stop stop.txt --top 3 part0.txt part1.txt
+brown 2000
+dog   2000
+fox   2000
+$ cargo run -- --ngrams 2 --top 3 --format csv part*.txt
+word,count
+brown fox,20000
+dog ok,20000
+fox jumps,20000
+$ cargo run -- --format json --top 2 part3.txt
+[
+  {"word": "the", "count": 2000},
+  {"word": "brown", "count": 1000}
+]
+$ cargo run nope.txt
+error: nope.txt: No such file or directory (os error 2)
+
+Each of the twenty test files has a thousand lines of 'line 1 of part 0: the quick brown fox jumps over the lazy dog, "ok"'. Counting them all in parallel gives exactly the same counts as counting them one after another, which is worth checking whenever work is split up between threads:
+
+let mut all = WordCounter::new().fold_case(true);
+all.add_files(&paths)?;
+let mut one_by_one = WordCounter::new().fold_case(true);
+for p in &paths {
+    one_by_one.add_file(p)?;
+}
+assert_eq!(all.counts, one_by_one.counts);
+*/
@@ -0,0 +1,335 @@
+// setutils.rs
+
+/* 'ToSet' in sets1.rs only ever makes a 'HashSet', so the order of the values is whatever the hash function says, and its set operations give iterators of references, which is where the awkward '&String' came from. There are other kinds of set worth having:
+
+- a 'BTreeSet', which keeps its values sorted, so printing it gives the same order every time
+- a counted set (often called a "bag" or "multiset"), which remembers how many times each value went in
+- an index set, which keeps the values in the order they were first added
+
+The first is in the standard library. The second and third we write ourselves.
+
+First, though, there is a better answer to the '&String' problem than 'cloned'. Both 'HashSet' and 'BTreeSet' implement the operators '|', '&', '-' and '^' on references, and these give a brand new set of owned values: union, intersection, difference and symmetric difference (the values in one set or the other, but not both). The values have to be 'Clone', since they are copied into the new set. */
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::hash::Hash;
+use std::ops::{BitAnd, BitOr, BitXor, Sub};
+
+fn make_set(words: &str) -> HashSet<String> {
+    words.split_whitespace().map(|s| s.to_string()).collect()
+}
+
+let fruit = make_set("apple orange pear");
+let colours = make_set("brown purple orange yellow");
+// intersect is HashSet<String>, no 'cloned' needed
+let intersect = &fruit & &colours;
+// {"orange"}
+
+/* So our own sets will have the same operators, returning owned sets, and we'll see how to write them below.
+
+The counted set is a map from each value to its count. We use a 'BTreeMap', so the values come out in order, as with 'BTreeSet'. 'len' is the number of different values, and 'total' counts the repeats too.
+
+The set operations need to say what happens to the counts. The usual rules are that a union takes the larger count, an intersection takes the smaller, and a difference subtracts the counts. (Think of two shopping baskets: the intersection is what is in both, and 'saturating_sub' stops at zero rather than going negative.) The symmetric difference is how far apart the two counts are. These all work the same way, going over every value in either set, so they share 'combine', which is given the rule as a closure. A value whose count comes out as zero is not in the result. */
+
+// A set which counts how many times each value was added
+#[derive(Clone, PartialEq, Eq, Default)]
+struct CountedSet<T: Ord> {
+    counts: BTreeMap<T,usize>
+}
+
+impl <T: Ord + Clone> CountedSet<T> {
+    fn new() -> CountedSet<T> {
+        CountedSet{counts: BTreeMap::new()}
+    }
+
+    fn insert(&mut self, value: T) {
+        *self.counts.entry(value).or_insert(0) += 1;
+    }
+
+    fn count(&self, value: &T) -> usize {
+        self.counts.get(value).cloned().unwrap_or(0)
+    }
+
+    // the number of different values
+    fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    // the number of values, counting repeats
+    fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&T,usize)> {
+        self.counts.iter().map(|(v, &n)| (v, n))
+    }
+
+    // Combine the counts of every value in either set, dropping zeros
+    fn combine<F>(&self, other: &CountedSet<T>, f: F) -> CountedSet<T>
+    where F: Fn(usize,usize) -> usize {
+        let mut counts = BTreeMap::new();
+        for v in self.counts.keys().chain(other.counts.keys()) {
+            let n = f(self.count(v), other.count(v));
+            if n > 0 {
+                counts.insert(v.clone(), n);
+            }
+        }
+        CountedSet{counts: counts}
+    }
+
+    fn union(&self, other: &CountedSet<T>) -> CountedSet<T> {
+        self.combine(other, |a, b| a.max(b))
+    }
+
+    fn intersection(&self, other: &CountedSet<T>) -> CountedSet<T> {
+        self.combine(other, |a, b| a.min(b))
+    }
+
+    fn difference(&self, other: &CountedSet<T>) -> CountedSet<T> {
+        self.combine(other, |a, b| a.saturating_sub(b))
+    }
+
+    fn symmetric_difference(&self, other: &CountedSet<T>) -> CountedSet<T> {
+        self.combine(other, |a, b| a.max(b) - a.min(b))
+    }
+}
+
+/* A derived 'Debug' would show the 'counts' field by name, which isn't very interesting. 'debug_map' gives the same '{key: value}' form as a map, which reads nicely for a set with counts. */
+
+impl <T: Ord + fmt::Debug> fmt::Debug for CountedSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.counts.iter()).finish()
+    }
+}
+
+/* The index set keeps its values in a 'Vec', which gives the order, and also keeps a 'HashMap' from each value to its position, so that 'contains' doesn't have to search the vector. That means each value is stored twice, so it has to be 'Clone'. Adding a value which is already there does nothing, just as with 'HashSet::insert', and returns 'false' to say so.
+
+The operations keep the order too. A union has the first set's values and then the new ones from the second, and the others keep the order of the first set. The symmetric difference is the values only in the first set, followed by those only in the second. Since an index set can be made from an iterator, each of these is just a filter and a 'collect'. */
+
+// A set which remembers the order in which values were first added
+#[derive(Clone, Default)]
+struct IndexSet<T: Eq + Hash> {
+    values: Vec<T>,
+    index: HashMap<T,usize>
+}
+
+impl <T: Eq + Hash + Clone> IndexSet<T> {
+    fn new() -> IndexSet<T> {
+        IndexSet{values: Vec::new(), index: HashMap::new()}
+    }
+
+    // 'true' if the value was not already there
+    fn insert(&mut self, value: T) -> bool {
+        if self.index.contains_key(&value) {
+            return false;
+        }
+        self.index.insert(value.clone(), self.values.len());
+        self.values.push(value);
+        true
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        self.index.contains_key(value)
+    }
+
+    fn position(&self, value: &T) -> Option<usize> {
+        self.index.get(value).cloned()
+    }
+
+    fn get(&self, i: usize) -> Option<&T> {
+        self.values.get(i)
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn iter(&self) -> std::slice::Iter<'_,T> {
+        self.values.iter()
+    }
+
+    fn union(&self, other: &IndexSet<T>) -> IndexSet<T> {
+        self.iter().chain(other.iter()).cloned().collect()
+    }
+
+    fn intersection(&self, other: &IndexSet<T>) -> IndexSet<T> {
+        self.iter().filter(|v| other.contains(v)).cloned().collect()
+    }
+
+    fn difference(&self, other: &IndexSet<T>) -> IndexSet<T> {
+        self.iter().filter(|v| ! other.contains(v)).cloned().collect()
+    }
+
+    fn symmetric_difference(&self, other: &IndexSet<T>) -> IndexSet<T> {
+        self.difference(other).iter().chain(other.difference(self).iter()).cloned().collect()
+    }
+}
+
+impl <T: Eq + Hash + Clone> FromIterator<T> for IndexSet<T> {
+    fn from_iter<I: IntoIterator<Item=T>>(iter: I) -> IndexSet<T> {
+        let mut set = IndexSet::new();
+        for v in iter {
+            set.insert(v);
+        }
+        set
+    }
+}
+
+/* Should two index sets with the same values in a different order be equal? They are still the same set, so we say yes, and compare the values rather than deriving 'PartialEq', which would compare the vectors. If the order matters, 'a.iter().eq(b.iter())' compares the values in order. */
+
+// Sets are equal if they have the same values, in any order
+impl <T: Eq + Hash> PartialEq for IndexSet<T> {
+    fn eq(&self, other: &IndexSet<T>) -> bool {
+        self.values.len() == other.values.len() && self.values.iter().all(|v| other.index.contains_key(v))
+    }
+}
+
+impl <T: Eq + Hash + fmt::Debug> fmt::Debug for IndexSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.values.iter()).finish()
+    }
+}
+
+/* Now the operators. '&a | &b' calls the 'bitor' method of the 'BitOr' trait, implemented for '&CountedSet<T>', and so on for the others. That is four impls for each kind of set, all exactly alike apart from the names, which is a job for a macro. The trait bounds are passed in as a list of tokens ('tt'), since they are different for each set. */
+
+// '&a | &b' and friends, as for 'HashSet' and 'BTreeSet'
+macro_rules! set_operators {
+    ($set:ident, $($bound:tt)+) => {
+        impl <'a,T: $($bound)+> BitOr<&'a $set<T>> for &'a $set<T> {
+            type Output = $set<T>;
+            fn bitor(self, other: &$set<T>) -> $set<T> { self.union(other) }
+        }
+        impl <'a,T: $($bound)+> BitAnd<&'a $set<T>> for &'a $set<T> {
+            type Output = $set<T>;
+            fn bitand(self, other: &$set<T>) -> $set<T> { self.intersection(other) }
+        }
+        impl <'a,T: $($bound)+> Sub<&'a $set<T>> for &'a $set<T> {
+            type Output = $set<T>;
+            fn sub(self, other: &$set<T>) -> $set<T> { self.difference(other) }
+        }
+        impl <'a,T: $($bound)+> BitXor<&'a $set<T>> for &'a $set<T> {
+            type Output = $set<T>;
+            fn bitxor(self, other: &$set<T>) -> $set<T> { self.symmetric_difference(other) }
+        }
+    }
+}
+
+set_operators!(CountedSet, Ord + Clone);
+set_operators!(IndexSet, Eq + Hash + Clone);
+
+/* The shortcuts are like 'ToSet', but they are all in one trait. Each method has its own 'where' clause, since a 'BTreeSet' needs 'Ord' and an index set needs 'Hash'; a method can only be called if its bounds are met. The methods have default bodies, so the blanket impl for every iterator is empty. */
+
+trait ToSets<T>: Iterator<Item=T> + Sized {
+    fn to_btree_set(self) -> BTreeSet<T> where T: Ord {
+        self.collect()
+    }
+
+    fn to_counted_set(self) -> CountedSet<T> where T: Ord + Clone {
+        let mut set = CountedSet::new();
+        for v in self {
+            set.insert(v);
+        }
+        set
+    }
+
+    fn to_index_set(self) -> IndexSet<T> where T: Eq + Hash + Clone {
+        self.collect()
+    }
+}
+
+impl <T,I> ToSets<T> for I where I: Iterator<Item=T> {}
+
+/* How alike are two sets? A common measure is the Jaccard similarity: the number of values they have in common, divided by the number of values in either. It is 1.0 for the same set and 0.0 for sets with nothing in common. The size of the union is the sizes of the two sets less what they have in common (which would otherwise be counted twice), so there is no need to build the union just to count it.
+
+For counted sets the same idea works with counts: the total of the intersection over the total of the union. Two empty sets are taken to be the same, rather than dividing zero by zero. */
+
+// How alike two sets are: the size of the intersection over the size of the union
+trait Jaccard {
+    fn jaccard(&self, other: &Self) -> f64;
+}
+
+fn ratio(common: usize, all: usize) -> f64 {
+    // two empty sets are the same
+    if all == 0 {1.0} else {common as f64 / all as f64}
+}
+
+impl <T: Eq + Hash> Jaccard for HashSet<T> {
+    fn jaccard(&self, other: &HashSet<T>) -> f64 {
+        let common = self.intersection(other).count();
+        ratio(common, self.len() + other.len() - common)
+    }
+}
+
+impl <T: Ord> Jaccard for BTreeSet<T> {
+    fn jaccard(&self, other: &BTreeSet<T>) -> f64 {
+        let common = self.intersection(other).count();
+        ratio(common, self.len() + other.len() - common)
+    }
+}
+
+impl <T: Eq + Hash + Clone> Jaccard for IndexSet<T> {
+    fn jaccard(&self, other: &IndexSet<T>) -> f64 {
+        let common = self.iter().filter(|v| other.contains(v)).count();
+        ratio(common, self.len() + other.len() - common)
+    }
+}
+
+// With counts, the smaller count is in common and the larger is in the union
+impl <T: Ord + Clone> Jaccard for CountedSet<T> {
+    fn jaccard(&self, other: &CountedSet<T>) -> f64 {
+        ratio(self.intersection(other).total(), self.union(other).total())
+    }
+}
+
+/* Back to the fruit and colours. As a 'BTreeSet', the values always come out in order. Only "orange" is in both, out of six different words, so the similarity is 1/6. */
+
+let fruit = "apple orange pear orange".split_whitespace().to_btree_set();
+let colours = "brown purple orange yellow".split_whitespace().to_btree_set();
+println!("{:?}", fruit);
+println!("{:?} {:?}", &fruit & &colours, &fruit | &colours);
+println!("{:?} {:?}", &fruit - &colours, &fruit ^ &colours);
+println!("{:.3}", fruit.jaccard(&colours));
+// {"apple", "orange", "pear"}
+// {"orange"} {"apple", "brown", "orange", "pear", "purple", "yellow"}
+// {"apple", "pear"} {"apple", "brown", "pear", "purple", "yellow"}
+// 0.167
+
+/* A counted set remembers that there were three oranges. */
+
+let basket = "apple orange pear orange apple orange".split_whitespace().to_counted_set();
+let stall = "orange orange pear pear plum".split_whitespace().to_counted_set();
+println!("{:?} {} {} {}", basket, basket.len(), basket.total(), basket.count(&"orange"));
+println!("{:?}", &basket | &stall);
+println!("{:?}", &basket & &stall);
+println!("{:?}", &basket - &stall);
+println!("{:?}", &basket ^ &stall);
+println!("{:.3}", basket.jaccard(&stall));
+// {"apple": 2, "orange": 3, "pear": 1} 3 6 3
+// {"apple": 2, "orange": 3, "pear": 2, "plum": 1}
+// {"orange": 2, "pear": 1}
+// {"apple": 2, "orange": 1}
+// {"apple": 2, "orange": 1, "pear": 1, "plum": 1}
+// 0.375
+
+/* They have two oranges and a pear in common, out of eight things in the union, which is 3/8.
+
+An index set keeps the words in the order they were first seen, which is what you want for something like the list of words in a document. */
+
+let fruit = "pear apple orange apple".split_whitespace().to_index_set();
+let colours = "yellow orange brown purple".split_whitespace().to_index_set();
+println!("{:?} {:?} {:?}", fruit, fruit.position(&"orange"), fruit.get(0));
+println!("{:?}", &fruit | &colours);
+println!("{:?}", &fruit & &colours);
+println!("{:?}", &fruit - &colours);
+println!("{:?}", &fruit ^ &colours);
+println!("{:.3}", fruit.jaccard(&colours));
+// {"pear", "apple", "orange"} Some(2) Some("pear")
+// {"pear", "apple", "orange", "yellow", "brown", "purple"}
+// {"orange"}
+// {"pear", "apple"}
+// {"pear", "apple", "yellow", "brown", "purple"}
+// 0.167
+
+// the same set, but not in the same order
+assert_eq!(&fruit | &colours, &colours | &fruit);
+assert!(! (&fruit | &colours).iter().eq((&colours | &fruit).iter()));
@@ -75,4 +75,4 @@ got: PING 192.168.0.5 (192.168.0.5) 56(84) bytes of data.
 64 bytes from 192.168.0.5: icmp_seq=1 ttl=64 time=207 ms
 ...
 
-The active addresses come through pretty fast within the first half-second, and we then wait for the negative results to come in. You can now proceed to scrape things like ping times from the output, although this would only work on Linux. ping is universal, but the exact output format is different for each platform. To do better we need to use the cross-platform Rust networking API, and so let's move onto 'Networking'. */
\ No newline at end of file
+The active addresses come through pretty fast within the first half-second, and we then wait for the negative results to come in. You can now proceed to scrape things like ping times from the output, although this would only work on Linux. ping is universal, but the exact output format is different for each platform. To do better we need to use the cross-platform Rust networking API, and so let's move onto 'Networking'. (scanner.rs comes back to this problem, and finds the machines which are up with TCP connections instead.) */
\ No newline at end of file
@@ -0,0 +1,233 @@
+// cliserver.rs
+
+/* The interactive prompt from sets2.rs (and its improvements in clihelp.rs, clilines.rs, cliargs.rs and clitokens.rs) is a good way to poke at a running program. It would be even better if we could poke at it from somewhere else, and if several people could do it at once. So let's serve the same commands over TCP, as in server2.rs, with a thread for each connection.
+
+Two questions have to be answered first. What does each connection get for itself, and what is shared? Each connection is a session, with its own variables, so that one user's '$name' doesn't change another's; but they all see the same program data. Shared data which is changed from several threads goes in an 'Arc<Mutex<_>>', as in thread8.rs.
+
+And where do the commands come from? The closures in a 'Cli' are not 'Send', so a 'Cli' can't be moved into another thread; and they work on one 'D', which is the session. So instead of one 'Cli', the server is given a function which makes a fresh 'Cli' for a session, given the shared data. It runs inside the new thread, so nothing about the 'Cli' has to be 'Send'. Only the function itself is shared between threads, which is why it has to be 'Send + Sync'.
+
+Here is the session data, and the function which sets up its commands. Each command locks the shared data just long enough to read or change it.
+
+A command must not panic while it holds the lock. The panic would only end that client's thread, but the 'Mutex' would be left poisoned, and every other client's 'lock().unwrap()' would panic from then on. So 'add' uses 'checked_add', as 'powers' used 'checked_pow' in cliargs.rs, and an answer which doesn't fit in an 'i32' is an error for the one client who asked for it. */
+
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::collections::HashMap;
+use std::io;
+use std::io::prelude::*;
+
+struct Shared {
+    answer: i32
+}
+
+struct Session {
+    shared: Arc<Mutex<Shared>>,
+    vars: HashMap<String,String>
+}
+
+fn session(shared: Arc<Mutex<Shared>>) -> Cli<'static,Session> {
+    let mut cli = Cli::new(Session{shared: shared, vars: HashMap::new()});
+    cli.vars(|s| &s.vars);
+
+    cli.cmd("go", "n:int", "set the answer", |s,args| {
+        let (n,) = args.parse_args()?;
+        s.shared.lock().unwrap().answer = n;
+        ok(n)
+    });
+
+    cli.cmd("add", "n:int", "add to the answer", |s,args| {
+        let (n,): (i32,) = args.parse_args()?;
+        let mut shared = s.shared.lock().unwrap();
+        shared.answer = shared.answer.checked_add(n).ok_or(format!("{} + {} is too big", shared.answer, n))?;
+        ok(shared.answer)
+    });
+
+    cli.cmd("show", "", "show the answer", |s,_| {
+        ok(s.shared.lock().unwrap().answer)
+    });
+
+    cli.cmd("set", "name value", "set a variable for this session", |s,args| {
+        s.vars.insert(args[0].to_string(), args[1].to_string());
+        ok(())
+    });
+
+    cli.cmd("echo", "text", "show some text", |_,args| {
+        ok(args[0])
+    });
+    cli
+}
+
+/* Next, the protocol. The client sends one command per line. The reply has to tell the client where it ends, since a table or the help text is several lines long, and the client must not sit waiting for more lines which are never going to come. So the first line of the reply says how many lines of output follow:
+
+ok 2
+first line
+second line
+
+An error is a single line starting with "error: ". A command which gives no output gets "ok 0". That's all a client needs to know.
+
+The nice thing is that a connection is just another line source, like the ones in clilines.rs. It reads lines from the socket and reports results by writing them back, and the session is then simply 'cli.run'. We need separate handles for reading and writing, since the reader is wrapped in a 'BufReader'; 'try_clone' gives another handle to the same socket.
+
+Sending "quit" ends the session, like Ctrl-D at the interactive prompt. It says goodbye first, so that the client gets a proper reply. If writing a reply fails, then the client has gone away, and 'report' returns 'false' to end the session. */
+
+struct Connection {
+    reader: io::BufReader<TcpStream>,
+    writer: TcpStream
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Connection> {
+        let writer = stream.try_clone()?;
+        Ok(Connection{reader: io::BufReader::new(stream), writer: writer})
+    }
+
+    fn respond(&mut self, res: &CliResult) -> io::Result<()> {
+        match *res {
+            Ok(Output::Nothing) => writeln!(self.writer, "ok 0"),
+            Ok(ref out) => {
+                let text = out.to_string();
+                writeln!(self.writer, "ok {}", text.lines().count())?;
+                for line in text.lines() {
+                    writeln!(self.writer, "{}", line)?;
+                }
+                Ok(())
+            },
+            // an error must fit on its one line
+            Err(ref e) => writeln!(self.writer, "error: {}", e.replace('\n', " "))
+        }
+    }
+}
+
+impl LineSource for Connection {
+    fn read_line(&mut self, _prompt: &str) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim();
+        if line == "quit" {
+            self.respond(&ok("bye"))?;
+            return Ok(None);
+        }
+        Ok(Some(line.to_string()))
+    }
+
+    fn report(&mut self, res: &CliResult) -> bool {
+        // if the client has gone away, the session is over
+        self.respond(res).is_ok()
+    }
+}
+
+/* The server loop is server2.rs again, except that each connection is handled in its own thread, so that a slow client doesn't hold up everyone else. The shared data and the session function are both in an 'Arc', and each thread gets its own clone of each. A connection which fails only ends its own thread. */
+
+fn serve<D,S,F>(listener: TcpListener, data: D, session: F)
+where D: Send + 'static, F: Fn(Arc<Mutex<D>>) -> Cli<'static,S> + Send + Sync + 'static {
+    let data = Arc::new(Mutex::new(data));
+    let session = Arc::new(session);
+    for connection in listener.incoming() {
+        let stream = match connection {
+            Ok(stream) => stream,
+            Err(e) => {
+                println!("connection failed {}", e);
+                continue;
+            }
+        };
+        let data = data.clone();
+        let session = session.clone();
+        thread::spawn(move || {
+            let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or("unknown".to_string());
+            println!("{} connected", peer);
+            let mut cli = session(data);
+            match Connection::new(stream).and_then(|mut c| cli.run(&mut c)) {
+                Ok(()) => println!("{} left", peer),
+                Err(e) => println!("{} failed: {}", peer, e)
+            }
+        });
+    }
+}
+
+fn main() {
+    let listener = TcpListener::bind("127.0.0.1:8000").expect("could not start server");
+    serve(listener, Shared{answer: 42}, session);
+}
+
+/* A client sends a line, reads the status line, and then reads as many lines as it says. The result is an 'io::Result' for the connection, holding a 'Result' for the command. */
+
+// The client side: send a line, and read back the response
+fn ask(reader: &mut io::BufReader<TcpStream>, line: &str) -> io::Result<Result<Vec<String>,String>> {
+    writeln!(reader.get_mut(), "{}", line)?;
+    let mut status = String::new();
+    if reader.read_line(&mut status)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "server closed the connection"));
+    }
+    let status = status.trim_end();
+    if let Some(e) = status.strip_prefix("error: ") {
+        return Ok(Err(e.to_string()));
+    }
+    let n: usize = status.strip_prefix("ok ").and_then(|n| n.parse().ok())
+        .ok_or(io::Error::new(io::ErrorKind::InvalidData, format!("bad response '{}'", status)))?;
+    let mut lines = Vec::new();
+    for _ in 0..n {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        lines.push(line.trim_end().to_string());
+    }
+    Ok(Ok(lines))
+}
+
+/* To try it out in one program, the server can run in a thread of its own. Binding to port 0 lets the system pick a free port, and 'local_addr' tells us which one it picked. Then two clients, Alice and Bob, take turns. */
+
+let listener = TcpListener::bind("127.0.0.1:0")?;
+let addr = listener.local_addr()?;
+thread::spawn(move || serve(listener, Shared{answer: 42}, session));
+
+let mut alice = io::BufReader::new(TcpStream::connect(addr)?);
+let mut bob = io::BufReader::new(TcpStream::connect(addr)?);
+println!("{:?}", ask(&mut alice, "show")?);
+println!("{:?}", ask(&mut alice, "set name Alice")?);
+println!("{:?}", ask(&mut bob, "set name Bob")?);
+println!("{:?}", ask(&mut alice, "go 10")?);
+println!("{:?}", ask(&mut bob, "show")?);
+println!("{:?}", ask(&mut alice, "echo \"hello $name\"")?);
+println!("{:?}", ask(&mut bob, "echo \"hello $name\"")?);
+println!("{:?}", ask(&mut bob, "go x")?);
+println!("{:?}", ask(&mut alice, "quit")?);
+// 127.0.0.1:57260 connected
+// 127.0.0.1:57266 connected
+// Ok(["42"])
+// Ok([])
+// Ok([])
+// Ok(["10"])
+// Ok(["10"])
+// Ok(["hello Alice"])
+// Ok(["hello Bob"])
+// Err("'x' is not a valid integer for 'n'")
+// 127.0.0.1:57260 left
+// Ok(["bye"])
+
+/* The answer which Alice set is the answer which Bob sees, but each of them has their own '$name'. (The "connected" and "left" lines come from the server threads, so exactly where they turn up among the others depends on timing.)
+
+Now for a harder test: eight clients at once, each adding one to the answer a hundred times. 'add' reads the answer, changes it and writes it back, all with the same lock held. If it took the lock once to read and again to write, two clients could both read 10 and both write 11, and one of the additions would be lost. So if the mutex does its job, the answer ends up exactly 800 more than it was, and each client sees the answer go up every time it adds. */
+
+let handles: Vec<_> = (0..8).map(|_| thread::spawn(move || -> io::Result<()> {
+    let mut c = io::BufReader::new(TcpStream::connect(addr)?);
+    let mut last = 0;
+    for _ in 0..100 {
+        let answer: i32 = ask(&mut c, "add 1")?.unwrap()[0].parse().unwrap();
+        assert!(answer > last);
+        last = answer;
+    }
+    ask(&mut c, "quit")?.unwrap();
+    Ok(())
+})).collect();
+for h in handles {
+    h.join().unwrap()?;
+}
+// Alice set it to 10, so no addition was lost
+assert_eq!(ask(&mut bob, "show")?, Ok(vec!["810".to_string()]));
+
+// Too big is an error, and the server carries on
+println!("{:?}", ask(&mut bob, "add 2147483647")?);
+println!("{:?}", ask(&mut bob, "add -1")?);
+// Err("810 + 2147483647 is too big")
+// Ok(["809"])
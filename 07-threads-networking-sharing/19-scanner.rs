@@ -0,0 +1,366 @@
+// scanner.rs
+
+/* The pipeliner examples looked for machines on the local network, first by running 'ping' through the shell and scraping its output (which only works with Linux's 'ping'), and then by resolving the addresses, which only checks that they are well-formed and never goes near the network. Now that we have the networking API, we can do the job properly, and find out which machines are there and which services they offer.
+
+'ping' uses ICMP, which needs special privileges to send. But there is a simple test anyone can do: try to open a TCP connection to a port. There are three possible answers. If something is listening, the connection is made and the port is "open". If the machine is there but nothing is listening, it refuses straight away and the port is "closed"; either way, the machine is up. And if nothing comes back at all, the port is "filtered": a firewall quietly threw our request away, or there is no machine there. 'TcpStream::connect' could wait for over a minute before giving up on a filtered port, so we use 'connect_timeout'.
+
+First, the range of addresses. The usual way to write one is CIDR notation: '192.168.0.0/24' means all the addresses whose first 24 bits match '192.168.0.0', so '192.168.0.0' to '192.168.0.255'. An IPv4 address is just a 32-bit number, and 'Ipv4Addr' converts to and from 'u32', so the range is a matter of masking bits. We only do IPv4; an IPv6 network usually has 2^64 addresses, which nobody is going to scan one at a time. */
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cidr {
+    network: Ipv4Addr,
+    prefix: u8
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Cidr,String> {
+        // a plain address is a range of one
+        let (addr, prefix) = s.split_once('/').unwrap_or((s, "32"));
+        let addr: Ipv4Addr = addr.parse().map_err(|_| format!("{}: bad address", s))?;
+        let prefix: u8 = match prefix.parse() {
+            Ok(p) if p <= 32 => p,
+            _ => return Err(format!("{}: bad prefix", s))
+        };
+        Ok(Cidr{network: Ipv4Addr::from(u32::from(addr) & Cidr::mask(prefix)), prefix: prefix})
+    }
+}
+
+/* The first and last addresses of a range are special: the first names the network itself, and the last is for broadcasting to all of it. So neither is a host, except in the tiny '/31' and '/32' ranges, which have no room for them. ('u32::MAX << 32' would overflow, which is why a '/0' mask is a special case.) */
+
+impl Cidr {
+    fn mask(prefix: u8) -> u32 {
+        if prefix == 0 {0} else {u32::MAX << (32 - prefix)}
+    }
+
+    fn contains(self, addr: Ipv4Addr) -> bool {
+        u32::from(addr) & Cidr::mask(self.prefix) == u32::from(self.network)
+    }
+
+    // The addresses of the hosts in the range
+    fn hosts(self) -> impl Iterator<Item=Ipv4Addr> {
+        let (first, last) = self.host_range();
+        (first..=last).map(Ipv4Addr::from)
+    }
+
+    // The first and last hosts, as numbers
+    fn host_range(self) -> (u32, u32) {
+        let first = u32::from(self.network);
+        let last = first | ! Cidr::mask(self.prefix);
+        if self.prefix < 31 {(first + 1, last - 1)} else {(first, last)}
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+println!("{:?}", "192.168.0.17/24".parse::<Cidr>());
+println!("{:?} {:?} {:?}", "10.0.0.1".parse::<Cidr>(), "10.0.0.1/33".parse::<Cidr>(), "10.0.0/8".parse::<Cidr>());
+let c: Cidr = "192.168.0.0/30".parse().unwrap();
+println!("{} {:?} {}", c, c.hosts().collect::<Vec<_>>(), c.contains("192.168.0.3".parse().unwrap()));
+println!("{}", "10.0.0.0/8".parse::<Cidr>().unwrap().hosts().count());
+// Ok(Cidr { network: 192.168.0.0, prefix: 24 })
+// Ok(Cidr { network: 10.0.0.1, prefix: 32 }) Err("10.0.0.1/33: bad prefix") Err("10.0.0/8: bad address")
+// 192.168.0.0/30 [192.168.0.1, 192.168.0.2] true
+// 16777214
+
+/* A probe is one attempt to connect, and the result says what happened to it and how long it took. The errors are told apart by their 'ErrorKind', as with 'NotFound' in run.rs. A refusal is 'ConnectionRefused'; running out of time is 'TimedOut'. Anything else, such as 'HostUnreachable' when the local network knows there's nobody at that address, is kept as it is. The connection itself is dropped at once, since we only wanted to know it could be made. */
+
+#[derive(Debug, Clone, PartialEq)]
+enum PortState {
+    Open,
+    Closed,
+    Filtered,
+    Error(String)
+}
+
+#[derive(Debug, Clone)]
+struct Probe {
+    port: u16,
+    state: PortState,
+    latency: Duration
+}
+
+fn probe(addr: SocketAddr, timeout: Duration) -> Probe {
+    let start = Instant::now();
+    let state = match TcpStream::connect_timeout(&addr, timeout) {
+        Ok(_) => PortState::Open,
+        Err(e) => match e.kind() {
+            io::ErrorKind::ConnectionRefused => PortState::Closed,
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => PortState::Filtered,
+            _ => PortState::Error(e.to_string())
+        }
+    };
+    Probe{port: addr.port(), state: state, latency: start.elapsed()}
+}
+
+/* The report for a host has a probe for each port, in order. A host is up if any port answered, even with a refusal. */
+
+#[derive(Debug)]
+struct HostReport {
+    addr: Ipv4Addr,
+    probes: Vec<Probe>
+}
+
+impl HostReport {
+    fn up(&self) -> bool {
+        self.probes.iter().any(|p| p.state == PortState::Open || p.state == PortState::Closed)
+    }
+
+    fn open(&self) -> Vec<u16> {
+        self.probes.iter().filter(|p| p.state == PortState::Open).map(|p| p.port).collect()
+    }
+}
+
+impl fmt::Display for HostReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.addr, if self.up() {"up"} else {"down"})?;
+        for p in &self.probes {
+            let state = match p.state {
+                PortState::Open => "open".to_string(),
+                PortState::Closed => "closed".to_string(),
+                PortState::Filtered => "filtered".to_string(),
+                PortState::Error(ref e) => e.clone()
+            };
+            write!(f, "\n  {:>5} {:<10} {:?}", p.port, state, p.latency)?;
+        }
+        Ok(())
+    }
+}
+
+/* Most of a scan is spent waiting, for the timeouts of filtered ports and for addresses with nobody there, so probes have to run in parallel. But the pipeliner example started a thread for every address, and a '/16' range with a few ports would mean hundreds of thousands of threads. So the 'Scanner' has a fixed number of threads, which take turns at a shared list of jobs until there are none left.
+
+The jobs don't even need to be in a list. Job 'i' is port 'i % nports' on host 'i / nports', so all the threads need to share is a counter. 'fetch_add' gives each thread the next number and moves the counter on, in one step, so no two threads ever get the same job. The results come back on a channel, as in thread6.rs. Each result carries the number of its host, so it can go straight into its place in the report, however the threads finish.
+
+'scan' doesn't wait for the whole range, and doesn't keep a report for every host in it. A '/8' has nearly seventeen million hosts, and a '/0' over four thousand million, which is far too many reports to hold at once, and would mean waiting for the last one before seeing the first. So 'scan' starts the threads and hands back an iterator over the reports. Since the jobs are taken in order, the only reports held are for the few hosts which the threads are busy with, and for any finished ones which are waiting for a slower host before them. A host is given out as soon as all its ports are done, as long as the hosts before it have been given out, so the reports still come in the order of the addresses. If the caller stops early and drops the 'Scan', the threads find that nobody is listening on the channel any more, and stop too. */
+
+struct Scanner {
+    ports: Vec<u16>,
+    timeout: Duration,
+    threads: usize
+}
+
+impl Scanner {
+    fn new(ports: &[u16]) -> Scanner {
+        Scanner{ports: ports.to_vec(), timeout: Duration::from_millis(500), threads: 64}
+    }
+
+    fn timeout(mut self, timeout: Duration) -> Scanner {
+        self.timeout = timeout;
+        self
+    }
+
+    fn threads(mut self, threads: usize) -> Scanner {
+        self.threads = threads.max(1);
+        self
+    }
+    ...
+}
+
+fn scan(&self, range: Cidr) -> Scan {
+    let (first, last) = range.host_range();
+    let njobs = (last - first) as usize * self.ports.len() + self.ports.len();
+    let next = Arc::new(AtomicUsize::new(0));
+    let ports = Arc::new(self.ports.clone());
+    let (tx, rx) = mpsc::channel();
+    for _ in 0..self.threads.min(njobs) {
+        let (next, ports, tx, timeout) = (next.clone(), ports.clone(), tx.clone(), self.timeout);
+        thread::spawn(move || loop {
+            let job = next.fetch_add(1, Ordering::SeqCst);
+            if job >= njobs {
+                break;
+            }
+            let host = Ipv4Addr::from(first + (job / ports.len()) as u32);
+            let probe = probe(SocketAddr::new(IpAddr::V4(host), ports[job % ports.len()]), timeout);
+            // the 'Scan' has been dropped, so nobody wants the rest
+            if tx.send((job / ports.len(), probe)).is_err() {
+                break;
+            }
+        });
+    }
+    // only the threads have senders now, so 'rx' ends when they are all done
+    drop(tx);
+    Scan{rx: rx, first: first, nports: self.ports.len(), partial: HashMap::new(), next: 0}
+}
+
+// The reports of a scan, in the order of the addresses, as each host is finished
+struct Scan {
+    rx: mpsc::Receiver<(usize, Probe)>,
+    first: u32,
+    nports: usize,
+    // hosts which are still being probed, or are waiting for an earlier one
+    partial: HashMap<usize, HostReport>,
+    next: usize
+}
+
+impl Iterator for Scan {
+    type Item = HostReport;
+
+    fn next(&mut self) -> Option<HostReport> {
+        loop {
+            if self.partial.get(&self.next).is_some_and(|r| r.probes.len() == self.nports) {
+                let mut report = self.partial.remove(&self.next).unwrap();
+                report.probes.sort_by_key(|p| p.port);
+                self.next += 1;
+                return Some(report);
+            }
+            let (host, probe) = self.rx.recv().ok()?;
+            let addr = Ipv4Addr::from(self.first + host as u32);
+            self.partial.entry(host).or_insert(HostReport{addr: addr, probes: Vec::new()}).probes.push(probe);
+        }
+    }
+}
+
+/* Scanning other people's machines is rude at best, so we'll try it on our own. A 'TcpListener' bound to port 0 gets any free port, and 'local_addr' says which. A listener is open even if it never calls 'accept', since the operating system completes the connection and keeps it waiting. A port which had a listener which has since been dropped is almost certainly closed. On Linux the whole of '127.0.0.0/8' is the local machine, but our listeners are only bound to '127.0.0.1', so '127.0.0.2' refuses everything. (The port numbers will be different every time.) */
+
+let open1 = TcpListener::bind("127.0.0.1:0")?;
+let open2 = TcpListener::bind("127.0.0.1:0")?;
+let closed = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+let ports = [open1.local_addr()?.port(), open2.local_addr()?.port(), closed];
+println!("{:?}", ports);
+let scanner = Scanner::new(&ports).timeout(Duration::from_millis(200)).threads(8);
+for r in scanner.scan("127.0.0.0/30".parse().unwrap()) {
+    println!("{}", r);
+    println!("{:?}", r.open());
+}
+// [42035, 39251, 46847]
+// 127.0.0.1 up
+//   39251 open       8.603µs
+//   42035 open       67.491µs
+//   46847 closed     15.163µs
+// [39251, 42035]
+// 127.0.0.2 up
+//   39251 closed     3.926µs
+//   42035 closed     5.008µs
+//   46847 closed     3.846µs
+// []
+
+/* A filtered port is harder to make at home, without changing the firewall. But a listener which never accepts has a queue of waiting connections, and once the queue is full, new requests are dropped without an answer, which is just what a firewall does. Linux queues 128 (plus one), so the 130th connection times out. */
+
+// A listener whose queue of waiting connections is full, so that it ignores new ones
+fn full_listener() -> io::Result<(TcpListener, Vec<TcpStream>)> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let mut waiting = Vec::new();
+    while let Ok(s) = TcpStream::connect_timeout(&addr, Duration::from_millis(50)) {
+        waiting.push(s);
+    }
+    Ok((listener, waiting))
+}
+
+/* With four filtered ports, each probe waits out the full timeout, and the number of threads makes all the difference: one thread takes four timeouts, two take two, and with enough threads it's over in one. */
+
+let mut full = Vec::new();
+for _ in 0..4 {
+    full.push(full_listener()?);
+}
+println!("{}", full[0].1.len());
+let mut fports: Vec<u16> = full.iter().map(|f| f.0.local_addr().unwrap().port()).collect();
+fports.push(ports[0]);
+for n in [1, 2, 8] {
+    let start = Instant::now();
+    let scanner = Scanner::new(&fports).timeout(Duration::from_millis(200)).threads(n);
+    let report = scanner.scan("127.0.0.1".parse().unwrap()).next().unwrap();
+    println!("{}", report);
+    println!("{} {:?}", n, start.elapsed());
+}
+// 129
+// 127.0.0.1 up
+//   36271 filtered   200.419057ms
+//   37143 filtered   200.42772ms
+//   39791 filtered   200.447078ms
+//   42035 open       84.296µs
+//   44473 filtered   200.393478ms
+// 1 802.085489ms
+// ...
+// 2 401.073536ms
+// ...
+// 8 200.653238ms
+
+/* A big range is no problem, since the reports come out as they are ready. Here are the first three hosts of '127.0.0.0/8'; after that the 'Scan' is dropped, and the threads stop. */
+
+let start = Instant::now();
+for r in Scanner::new(&ports[..1]).scan("127.0.0.0/8".parse().unwrap()).take(3) {
+    println!("{}", r);
+}
+println!("{:?}", start.elapsed());
+// 127.0.0.1 up
+//   42035 open       15.803µs
+// 127.0.0.2 up
+//   42035 closed     5.739µs
+// 127.0.0.3 up
+//   42035 closed     4.366µs
+// 5.401311ms
+
+/* Finally, a command-line scanner, which takes a range and a list of ports, and only reports the hosts which are up, as soon as each one is finished. Ports can be given singly or as ranges. */
+
+// Ports like "22,80,8000-8010"
+fn parse_ports(s: &str) -> Result<Vec<u16>,String> {
+    let mut ports = Vec::new();
+    for part in s.split(',') {
+        let bad = || format!("{}: bad port", part);
+        let (lo, hi) = part.split_once('-').unwrap_or((part, part));
+        let lo: u16 = lo.trim().parse().map_err(|_| bad())?;
+        let hi: u16 = hi.trim().parse().map_err(|_| bad())?;
+        // port 0 means "any port" to the system, so it can't be probed
+        if lo == 0 || lo > hi {
+            return Err(bad());
+        }
+        ports.extend(lo..=hi);
+    }
+    ports.sort();
+    ports.dedup();
+    Ok(ports)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() != 2 {
+        eprintln!("usage: scanner <range> <ports>   e.g. scanner 192.168.0.0/24 22,80,8000-8010");
+        std::process::exit(1);
+    }
+    let (range, ports) = match (args[0].parse::<Cidr>(), parse_ports(&args[1])) {
+        (Ok(range), Ok(ports)) => (range, ports),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    for report in Scanner::new(&ports).scan(range) {
+        if report.up() {
+            println!("{}", report);
+        }
+    }
+}
+
+// $ python3 -m http.server 8000 --bind 127.0.0.1 &
+// $ scanner 127.0.0.0/30 22,80,8000
+// 127.0.0.1 up
+//      22 closed     77.696µs
+//      80 closed     5.799µs
+//    8000 open       34.422µs
+// 127.0.0.2 up
+//      22 closed     5.989µs
+//      80 closed     4.928µs
+//    8000 closed     4.296µs
+// $ scanner 127.0.0.0/33 80
+// 127.0.0.0/33: bad prefix
+// $ scanner 127.0.0.1 80-x
+// 80-x: bad port
+// $ scanner 127.0.0.1 0-10
+// 0-10: bad port
@@ -0,0 +1,216 @@
+// threadpool.rs
+
+/* thread5.rs, thread6.rs and thread7.rs start a new thread for every piece of work. That's fine for five pieces, but threads are not free: each has its own stack, and starting one takes time. With ten thousand pieces of work, we want a few threads which each do many of them. The pipeliner crate does this with 'with_threads(n)', and scanner.rs did it by hand, with a counter which threads took turns at. A thread pool does it once and for all: a fixed number of worker threads, and a queue of jobs for them to take from.
+
+Everything we need has already come up. A job is a closure which can be sent to another thread and called once, so it's a boxed 'FnOnce() + Send'; boxed, because every closure has a type of its own, and a queue can only hold one type. The queue is a channel. There is one snag: 'mpsc' means "multiple producer, single consumer", and a 'Receiver' can't be shared. But it can be put in a 'Mutex', as in thread8.rs, and then each worker locks it just long enough to take the next job.
+
+The channel is a 'sync_channel' with a fixed size, as at the end of thread6.rs. If jobs are submitted faster than the workers can do them, an ordinary channel would just grow, and a program reading a huge file and submitting a job per line could end up with the whole file in memory, waiting. With a bounded queue, 'submit' waits when the queue is full, which slows the producer down to the speed of the workers. This is called back-pressure. */
+
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+struct ThreadPool {
+    sender: Option<SyncSender<Job>>,
+    workers: Vec<thread::JoinHandle<()>>
+}
+
+/* A worker takes jobs until the channel is closed. The lock is taken inside one statement, so the guard is dropped at the end of it; the worker holds the lock while it waits for a job, but not while it runs one. (Writing 'while let Ok(job) = receiver.lock().unwrap().recv()' would keep the lock for the whole body of the loop, so only one job would run at a time.) */
+
+fn worker(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        // the lock is only held while waiting for a job, not while running it
+        let job = receiver.lock().unwrap().recv();
+        match job {
+            Ok(job) => job(),
+            Err(_) => break
+        }
+    }
+}
+
+/* 'submit' gives back a 'Handle', which is to a job what a 'JoinHandle' is to a thread. Each job gets a channel of its own for its result, and 'join' waits for it.
+
+A panic in a job must not kill the worker, or the pool would slowly lose its threads. 'catch_unwind' stops the panic and gives it back as an 'Err' holding the panic's value, which is just what 'thread::Result' is, and what 'JoinHandle::join' returns for a thread which panicked. So the panic is passed on to whoever asks for the result, and 'unwrap' will carry on panicking there. 'catch_unwind' wants to be sure the closure can't leave shared data half-changed; our closure is moved in and never seen again, so 'AssertUnwindSafe' tells it not to worry. If the 'Handle' has been dropped, nobody wants the result, and a failed 'send' doesn't matter. */
+
+impl ThreadPool {
+    fn new(threads: usize, queue: usize) -> ThreadPool {
+        let (sender, receiver) = mpsc::sync_channel::<Job>(queue);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..threads.max(1)).map(|_| {
+            let receiver = receiver.clone();
+            thread::spawn(move || worker(&receiver))
+        }).collect();
+        ThreadPool{sender: Some(sender), workers: workers}
+    }
+
+    fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    fn submit<T,F>(&self, f: F) -> Handle<T>
+    where T: Send + 'static, F: FnOnce() -> T + Send + 'static {
+        let (tx, rx) = mpsc::channel();
+        let job = Box::new(move || {
+            let res = panic::catch_unwind(AssertUnwindSafe(f));
+            let _ = tx.send(res);
+        });
+        self.sender.as_ref().unwrap().send(job).unwrap();
+        Handle{result: rx}
+    }
+    ...
+}
+
+struct Handle<T> {
+    result: Receiver<thread::Result<T>>
+}
+
+impl <T> Handle<T> {
+    fn join(self) -> thread::Result<T> {
+        self.result.recv().unwrap()
+    }
+}
+
+/* Shutting down is a matter of closing the channel, by dropping the only 'SyncSender'. The workers finish the jobs which are still in the queue, since 'recv' only fails once the channel is both closed and empty; then we join them. 'shutdown' is there to make the end explicit, and 'drop' does the same, so a pool never leaves threads behind. The 'sender' is an 'Option' for the same reason as 'out' in atomic.rs. */
+
+fn shutdown(mut self) {
+    self.stop();
+}
+
+fn stop(&mut self) {
+    self.sender = None;
+    for w in self.workers.drain(..) {
+        let _ = w.join();
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+let pool = ThreadPool::new(4, 8);
+let handles: Vec<Handle<u64>> = (0..10u64).map(|i| pool.submit(move || i * i)).collect();
+let squares: Vec<u64> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+println!("{:?}", squares);
+// [0, 1, 4, 9, 16, 25, 36, 49, 64, 81]
+
+/* The panic message is still printed, by the usual panic hook, but the panic comes back as an 'Err', and 'downcast_ref' gets the message out of it. The pool carries on with all four workers. */
+
+let h = pool.submit(|| -> i32 { panic!("no answer") });
+let ok = pool.submit(|| 42);
+let res = h.join();
+println!("{:?} {:?}", res.as_ref().map_err(|e| e.downcast_ref::<&str>()), ok.join());
+println!("{:?}", (0..8).map(|i| pool.submit(move || i)).map(|h| h.join().unwrap()).sum::<i32>());
+// thread '<unnamed>' panicked at src/main.rs:...:
+// no answer
+// Err(Some("no answer")) Ok(42)
+// 28
+
+/* Here is back-pressure at work, with two workers and room for two jobs in the queue. Each job takes 100ms. The first four are accepted at once: two start, and two wait in the queue. After that, 'submit' has to wait for a place in the queue, so the loop goes at the speed of the workers. */
+
+let small = ThreadPool::new(2, 2);
+let start = Instant::now();
+let mut handles = Vec::new();
+for i in 0..8 {
+    handles.push(small.submit(move || { thread::sleep(Duration::from_millis(100)); i }));
+    println!("submitted {} at {}ms", i, start.elapsed().as_millis() / 10 * 10);
+}
+let results: Vec<i32> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+println!("{:?} {}ms", results, start.elapsed().as_millis() / 10 * 10);
+// submitted 0 at 0ms
+// submitted 1 at 0ms
+// submitted 2 at 0ms
+// submitted 3 at 0ms
+// submitted 4 at 100ms
+// submitted 5 at 100ms
+// submitted 6 at 200ms
+// submitted 7 at 200ms
+// [0, 1, 2, 3, 4, 5, 6, 7] 400ms
+
+/* And a graceful shutdown: these jobs are still queued when 'shutdown' is called, but they all get done before it returns. */
+
+let (tx, rx) = mpsc::channel();
+let p = ThreadPool::new(2, 10);
+for i in 0..6 {
+    let tx = tx.clone();
+    p.submit(move || { thread::sleep(Duration::from_millis(50)); tx.send(i).unwrap(); });
+}
+p.shutdown();
+drop(tx);
+println!("{:?}", rx.iter().collect::<Vec<_>>());
+// [0, 1, 3, 2, 4, 5]
+
+/* The results of the pipeliner example came out in whatever order the threads finished. Often we want them in the order of the input, like 'map' would give. 'par_map' keeps the 'Handle's in a queue, in the order the jobs were submitted, and always waits for the oldest. A job which finishes early just waits in its channel until its turn comes.
+
+It mustn't submit the whole input at once, because the input might be huge, or never end. So it keeps a window of jobs on the go, twice as many as there are workers, so that a worker which finishes can start on another job at once. Every job calls the same 'f', so 'f' is shared in an 'Arc' and has to be 'Sync'. If a job panics, the panic is carried on into the caller with 'resume_unwind', just as a panic in the closure given to 'map' would be.
+
+As with 'Iterator', the method comes from a trait, which is implemented for every iterator, so 'par_map' can be called on any of them. */
+
+struct ParMap<'p,I,F,T> {
+    iter: I,
+    f: Arc<F>,
+    pool: &'p ThreadPool,
+    pending: VecDeque<Handle<T>>
+}
+
+impl <'p,I,F,T> Iterator for ParMap<'p,I,F,T>
+where I: Iterator, I::Item: Send + 'static, T: Send + 'static, F: Fn(I::Item) -> T + Send + Sync + 'static {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        while self.pending.len() < 2 * self.pool.threads() {
+            match self.iter.next() {
+                Some(x) => {
+                    let f = self.f.clone();
+                    self.pending.push_back(self.pool.submit(move || f(x)));
+                },
+                None => break
+            }
+        }
+        let handle = self.pending.pop_front()?;
+        match handle.join() {
+            Ok(res) => Some(res),
+            Err(payload) => panic::resume_unwind(payload)
+        }
+    }
+}
+
+trait ParMapExt: Iterator + Sized {
+    fn par_map<F,T>(self, pool: &ThreadPool, f: F) -> ParMap<'_,Self,F,T>
+    where F: Fn(Self::Item) -> T + Send + Sync + 'static {
+        ParMap{iter: self, f: Arc::new(f), pool: pool, pending: VecDeque::new()}
+    }
+}
+
+impl <I: Iterator> ParMapExt for I {}
+
+/* Longer words finish sooner here, but the lengths still come out in order. And a hundred jobs of 10ms take a quarter of a second on four workers, rather than a whole second. */
+
+let start = Instant::now();
+let lens: Vec<usize> = ["one", "three", "eleven", "a", "twenty"].iter()
+    .par_map(&pool, |s| { thread::sleep(Duration::from_millis(100 - 15 * s.len() as u64)); s.len() })
+    .collect();
+println!("{:?} {}ms", lens, start.elapsed().as_millis() / 10 * 10);
+let start = Instant::now();
+let total: u64 = (0..100u64).par_map(&pool, |x| { thread::sleep(Duration::from_millis(10)); x }).sum();
+println!("{} {}ms", total, start.elapsed().as_millis() / 10 * 10);
+// [3, 5, 6, 1, 6] 90ms
+// 4950 250ms
+
+let res = panic::catch_unwind(AssertUnwindSafe(|| {
+    (0..10).par_map(&pool, |x| if x == 5 { panic!("five") } else { x }).collect::<Vec<_>>()
+}));
+println!("{}", res.is_err());
+println!("{:?}", (0..3).par_map(&pool, |x| x + 1).collect::<Vec<_>>());
+// thread '<unnamed>' panicked at src/main.rs:...:
+// five
+// true
+// [1, 2, 3]
+
+/* The pool is borrowed by 'par_map', so it can't be shut down while the iterator is still in use; the borrow checker sees to that. */
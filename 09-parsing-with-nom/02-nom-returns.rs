@@ -1,42 +1,47 @@
-/* 'IResult' is different from the standard 'Result' type in that there are three possibilities:
+/* 'IResult' is just a standard 'Result'. On success you get a tuple of the remaining input and the value; on failure you get a 'nom::Err', which has three possibilities:
 
-* 'Done' - success - you get both the result and the remaining bytes
-* 'Error' - failed to parse - you get an error
+* 'Error' - failed to parse - a recoverable error, so 'alt' can go on to try something else
+* 'Failure' - failed to parse, and don't bother trying anything else
 * 'Incomplete' - more data needed
 
-We can write a generic 'dump' function that handles any return value that can be debug-printed. This also demonstrates the 'to_result' method which returns a regular 'Result'; this is probably the method you will use for most cases since it returns either the returned value or an error. */
+We can write a generic 'dump' function that handles any return value that can be debug-printed. This also demonstrates the 'finish' method which returns a regular 'Result' holding the remaining input and the value, or the error. This is probably the method you will use for most cases. */
 
-#[macro_use]
-extern crate nom;
-use nom::IResult;
-use std::str::from_utf8;
+use nom::{IResult, Parser, Finish};
+use nom::bytes::complete::tag;
+use nom::branch::alt;
 use std::fmt::Debug;
 
 fn dump<T: Debug>(res: IResult<&str,T>) {
     match res {
-      IResult::Done(rest, value) => {println!("Done {:?} {:?}",rest,value)},
-      IResult::Error(err) => {println!("Err {:?}",err)},
-      IResult::Incomplete(needed) => {println!("Needed {:?}",needed)}
+      Ok((rest, value)) => {println!("Done {:?} {:?}",rest,value)},
+      Err(nom::Err::Error(err)) => {println!("Err {:?}",err)},
+      Err(nom::Err::Failure(err)) => {println!("Failure {:?}",err)},
+      Err(nom::Err::Incomplete(needed)) => {println!("Needed {:?}",needed)}
     }
 }
 
 
 fn main() {
-    named!(get_greeting<&str,&str>,
-        ws!(
-            alt!( tag_s!("hi") | tag_s!("bye"))
-        )
-    );
+    // 'ws' as defined in the previous section
+    fn get_greeting(input: &str) -> IResult<&str,&str> {
+        ws(
+            alt((tag("hi"), tag("bye")))
+        ).parse(input)
+    }
 
     dump(get_greeting(" hi "));
     dump(get_greeting(" bye hi"));
     dump(get_greeting("  hola "));
 
-    println!("result {:?}", get_greeting(" bye  ").to_result());
+    println!("result {:?}", get_greeting(" bye  ").finish());
 }
-// Done Ok("") "hi"
-// Done Ok("hi") "bye"
-// Err Alt
-// result Ok("bye")
+// Done "" "hi"
+// Done "hi" "bye"
+// Err Error { input: "hola ", code: Tag }
+// result Ok(("", "bye"))
+
+/* Since it's a 'Result', all the usual methods work. If you don't care about any leftover input, then 'map' it away. */
+
+assert_eq!(get_greeting(" bye  ").map(|(_, v)| v), Ok("bye"));
 
-// Parsers returning any unparsed text, and being able to indicate that they don't have enough input characters to decide, is very useful for stream parsing, but normally you want to use 'to_result'
\ No newline at end of file
+// Parsers returning any unparsed text, and being able to indicate that they don't have enough input characters to decide, is very useful for stream parsing, but normally you want to use 'finish'. 'finish' will panic if it's given 'Incomplete', which can only come from the streaming parsers we'll meet next.
@@ -1,38 +1,51 @@
 // Continuing the greeting example and imagine that a greeting consists of "hi" or "bye", plus a name.
-named!(full_greeting<&str,(&str,&str)>,
-    // 'pair!' macro will collect the result of matching two parsers as a tuple
-    pair!(
+use nom::sequence::pair;
+use nom::character::complete::alpha1;
+
+fn full_greeting(input: &str) -> IResult<&str,(&str,&str)> {
+    // 'pair' will collect the result of matching two parsers as a tuple
+    pair(
         get_greeting,
-        // 'nom::alpha' matches a series of alphabetical characters
-        nom::alpha
-    )
-);
+        // 'alpha1' matches one or more alphabetical characters
+        alpha1
+    ).parse(input)
+}
 
-println!("result {:?}", full_greeting(" hi Bob  ").to_result());
-// result Ok(("hi", "Bob"))
+println!("result {:?}", full_greeting(" hi Bob  ").finish());
+// result Ok(("  ", ("hi", "Bob")))
 
 // Let's make the name optional. The second value of the tuple becomes an 'Option'.
-named!(full_greeting<&str, (&str,Option<&str>)>,
-    pair!(
+use nom::combinator::opt;
+
+fn full_greeting(input: &str) -> IResult<&str,(&str,Option<&str>)> {
+    pair(
         get_greeting,
-        opt!(nom::alpha)
-    )
-);
+        opt(alpha1)
+    ).parse(input)
+}
+
+println!("result {:?}", full_greeting(" hi Bob  ").finish());
+println!("result {:?}", full_greeting(" bye ?").finish());
+// result Ok(("  ", ("hi", Some("Bob"))))
+// result Ok(("?", ("bye", None)))
+
+assert_eq!(full_greeting(" hi Bob  "), Ok(("  ", ("hi", Some("Bob")))));
+assert_eq!(full_greeting(" bye ?"), Ok(("?", ("bye", None))));
+
 
-println!("result {:?}", full_greeting(" hi Bob  ").to_result());
-println!("result {:?}", full_greeting(" bye ?").to_result());
-// result Ok(("hi", Some("Bob")))
-// result Ok(("bye", None))
+/* It was straightforward to combine an existing parser for greetings with a parser that picks up names, and then it was easy to make that name optional. This is the power of Nom, and why it's called a "parser combinator library". You can build up your complicated parsers from simpler parsers, which you can test individually. At this point, the equivalent regex is starting to look like a Perl program; regexes do not combine well. Note that the parsers are plain functions, so 'get_greeting' can be passed to 'pair' directly.
 
+What about 'full_greeting(" bye ")'? We can't tell whether "bye" is going to be followed by a name, because there's nothing after it yet. A streaming parser, one which is fed a file chunk by chunk, would have to ask for more data here. Nom has two versions of its basic parsers. The ones in the 'complete' modules, which we have been using, assume that they have the whole input; the ones in the 'streaming' modules return 'Incomplete' when they run out. Older versions of Nom were streaming by default and you had to wrap parsers in 'complete!' to get the other behaviour. */
 
-/* It was straightforward to combine an existing parser for greetings with a parser that picks up names, and then it was easy to make that name optional. This is the power of Nom, and why it's called a "parser combinator library". You can build up your complicated parsers from simpler parsers, which you can test individually. At this point, the equivalent regex is starting to look like a Perl program; regexes do not combine well. We're not done yet though. 'full_greeting(" bye ")' will fail with an 'Incomplete' error. Nom knows that "bye" may be followed by a name and wants us to give it more data. This is how a streaming parser needs to work, so you can feed it a file chunk by chunk, but here we need to tell Nom that the input is complete. */
+println!("result {:?}", full_greeting(" bye "));
+// result Ok(("", ("bye", None)))
 
-named!(full_greeting<&str,(&str,Option<&str>)>,
-    pair!(
+fn streaming_greeting(input: &str) -> IResult<&str,(&str,Option<&str>)> {
+    pair(
         get_greeting,
-        opt!(complete!(nom::alpha))
-    )
-);
+        opt(nom::character::streaming::alpha1)
+    ).parse(input)
+}
 
-println!("result {:?}", full_greeting(" bye ").to_result());
-// result Ok(("bye", None)) */
\ No newline at end of file
+println!("result {:?}", streaming_greeting(" bye "));
+// result Err(Incomplete(Size(1)))
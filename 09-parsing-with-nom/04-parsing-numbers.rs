@@ -1,73 +1,78 @@
-// Nom provides a function 'digit' which matches a series of numerical digits. 
-// We use 'map!' to convert the string into an integer, and return the full 'Result' type.
-use nom::digit;
+// Nom provides a function 'digit1' which matches a series of one or more numerical digits.
+// We use 'map' to convert the string into an integer, and return the full 'Result' type.
+use nom::character::complete::digit1;
+use nom::combinator::map;
 use std::str::FromStr;
 use std::num::ParseIntError;
 
-named!(int8 <&str, Result<i8,ParseIntError>>,
-    map!(digit, FromStr::from_str)
-);
+fn int8(input: &str) -> IResult<&str, Result<i8,ParseIntError>> {
+    map(digit1, FromStr::from_str).parse(input)
+}
 
-named!(int32 <&str, Result<i32,ParseIntError>>,
-    map!(digit, FromStr::from_str)
-);
+fn int32(input: &str) -> IResult<&str, Result<i32,ParseIntError>> {
+    map(digit1, FromStr::from_str).parse(input)
+}
 
 println!("{:?}", int8("120"));
 println!("{:?}", int8("1200"));
 println!("{:?}", int8("x120"));
 println!("{:?}", int32("1202"));
 
-// Done("", Ok(120))
-// Done("", Err(ParseIntError { kind: Overflow }))
-// Error(Digit)
-// Done("", Ok(1202))
+// Ok(("", Ok(120)))
+// Ok(("", Err(ParseIntError { kind: PosOverflow })))
+// Err(Error(Error { input: "x120", code: Digit }))
+// Ok(("", Ok(1202)))
 
 /* What we get is a parser 'IResult' containing a conversion 'Result', there is more than one way to fail here. Note that the body of our converting function has exactly the same code; the actual conversion depends on the return type of the function. Integers may have a sign. We can capture integers as a pair, where the first value may be a sign, and the second value would be any digits following. */
 
-named!(signed_digits<&str, (Option<&str>,&str)>,
-    pair!(
-        opt!(alt!(tag_s!("+") | tag_s!("-"))),  // maybe sign?
-        digit
-    )
-);
+fn signed_digits(input: &str) -> IResult<&str, (Option<&str>,&str)> {
+    pair(
+        opt(alt((tag("+"), tag("-")))),  // maybe sign?
+        digit1
+    ).parse(input)
+}
 
 println!("signed {:?}", signed_digits("4"));
 println!("signed {:?}", signed_digits("+12"));
-// signed Done("", (None, "4"))
-// signed Done("", (Some("+"), "12"))
+// signed Ok(("", (None, "4")))
+// signed Ok(("", (Some("+"), "12")))
+
+assert_eq!(signed_digits("4"), Ok(("", (None, "4"))));
+assert_eq!(signed_digits("+12"), Ok(("", (Some("+"), "12"))));
 
-// When we aren't interested in the intermediate results, but just want all the matching input, then 'recognize!' is what you need.
+// When we aren't interested in the intermediate results, but just want all the matching input, then 'recognize' is what you need.
+use nom::combinator::recognize;
 
-named!(maybe_signed_digits<&str,&str>,
-    recognize!(signed_digits)
-);
+fn maybe_signed_digits(input: &str) -> IResult<&str,&str> {
+    recognize(signed_digits).parse(input)
+}
 
 println!("signed {:?}", maybe_signed_digits("+12"));
-// signed Done("", "+12")
+// signed Ok(("", "+12"))
 
-/* With this technique we can recognize floating-point numbers. Again we map to string slice from the byte slice over all these matches. 'tuple!' is the generalization of 'pair!', although we aren't interested in the generated tuple here. 'complete!' is needed to resolve the same problem we had with incomplete greetings; "12" is a valid number without the optional floating-point part. */
+/* With this technique we can recognize floating-point numbers. Again we get a string slice covering all these matches. A tuple of parsers is itself a parser which matches them all in sequence, the generalization of 'pair', although we aren't interested in the generated tuple here. We don't need anything like the old 'complete!' here; "12" is a valid number without the optional floating-point part, and the 'complete' version of 'digit1' knows that the end of the input is the end of the number. */
 
-named!(floating_point<&str,&str>,
-    recognize!(
-        tuple!(
+fn floating_point(input: &str) -> IResult<&str,&str> {
+    recognize(
+        (
             maybe_signed_digits,
-            opt!(complete!(pair!(
-                tag_s!("."),
-                digit
-            ))),
-            opt!(complete!(pair!(
-                alt!(tag_s!("e") | tag_s!("E")),
+            opt(pair(
+                tag("."),
+                digit1
+            )),
+            opt(pair(
+                alt((tag("e"), tag("E"))),
                 maybe_signed_digits
-            )))
+            ))
         )
-    )
-);
+    ).parse(input)
+}
 // By defining a helper macro, we get some passing tests
 // The test passes if 'floating_point' matches all of the string that it is given.
 
 macro_rules! nom_eq {
     ($p:expr,$e:expr) => (
-        assert_eq!($p($e).to_result().unwrap(), $e)
+        assert_eq!($p($e).finish().unwrap(), ("", $e))
     )
 }
 
@@ -80,9 +85,15 @@ nom_eq!(floating_point, "2.0e-6");
 
 // Although sometimes macros feel a little dirty, making your tests pretty is nice.
 
-// Then we can parse and convert floating point numbers. 
-// Here we'll take a risk and throw away the error
-named!(float64<f64>,
-    map_res!(floating_point, FromStr::from_str)
-);
-// Note how it's possible to build up complicated parsers step by step, testing each part in isolation first. That's a strong advantage of parser combinators over regexes. 
\ No newline at end of file
+// Then we can parse and convert floating point numbers.
+// 'map_res' turns a failed conversion into a parse error, so we don't end up with a 'Result' inside a 'Result'
+use nom::combinator::map_res;
+
+fn float64(input: &str) -> IResult<&str,f64> {
+    map_res(floating_point, FromStr::from_str).parse(input)
+}
+
+assert_eq!(float64("2.0e-6"), Ok(("", 2.0e-6)));
+assert_eq!(float64("-2343"), Ok(("", -2343.0)));
+
+// Note how it's possible to build up complicated parsers step by step, testing each part in isolation first. That's a strong advantage of parser combinators over regexes. (Nom does have a ready-made 'double' parser in 'nom::number::complete', but then we wouldn't have learned anything.)
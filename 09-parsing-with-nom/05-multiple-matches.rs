@@ -1,4 +1,4 @@
-/* We've seen 'pairs!' and 'tuple!' which capture a fixed number of matches as Rust tuples. There is also 'many0' and 'many1'; they both capture indefinite numbers of matches as vectors. The difference is that the first may capture 'zero or many' and the second 'one or many'. 'many1!(ws!(float64))' would parse "1 2 3" into 'vec![1.0,2.0,3.0]', but will fail on the empty string.
+/* We've seen 'pair' and tuples which capture a fixed number of matches as Rust tuples. There is also 'many0' and 'many1'; they both capture indefinite numbers of matches as vectors. The difference is that the first may capture 'zero or many' and the second 'one or many'. 'many1(ws(float64))' would parse "1 2 3" into 'vec![1.0,2.0,3.0]', but will fail on the empty string.
 
 'fold_many0' is a reducing operation. The match values are combined into a single value, using a binary operator. This is how Rust people did sums over iterators before 'sum' was added; this fold starts with an initial value (here zero) for the accumulator and keeps adding values to that accumulator using '+'. */
 
@@ -6,51 +6,62 @@ let res = [1,2,3].iter().fold(0,|acc,v| acc + v);
 println!("{}",res);
 // 6
 
-// The Nom equivalent
+// The Nom equivalent. The initial value is given by a closure, since the parser may be run many times and each run needs a fresh accumulator.
+use nom::multi::fold_many1;
 
-named!(fold_sum<&str,f64>,
-    fold_many1!(
-        ws!(float64),
-        0.0,
+fn fold_sum(input: &str) -> IResult<&str,f64> {
+    fold_many1(
+        ws(float64),
+        || 0.0,
         |acc, v| acc + v
-    )
-);
+    ).parse(input)
+}
 
-println!("fold {}", fold_sum("1 2 3").to_result().unwrap());
+println!("fold {}", fold_sum("1 2 3").finish().unwrap().1);
 //fold 6
 
-// Up to now, we've had to capture every expression, or just grab all matching bytes with 'recognize!':
+assert_eq!(fold_sum("1 2 3"), Ok(("", 6.0)));
+
+// Up to now, we've had to capture every expression, or just grab all matching bytes with 'recognize':
 
-named!(pointf<(f64,&[u8],f64)>,
-    tuple!(
+fn pointf(input: &str) -> IResult<&str,(f64,&str,f64)> {
+    (
         float64,
-        tag_s!(","),
+        tag(","),
         float64
-    )
-);
+    ).parse(input)
+}
 
-println!("got {:?}", nom_res!(pointf,"20,52.2").unwrap());
-//got (20, ",", 52.2)
+println!("got {:?}", pointf("20,52.2").finish().unwrap().1);
+//got (20.0, ",", 52.2)
 
-/* For more complicated expressions, capturing the results of all the parsers leads to untidy types. 'do_parse!' lets you extract only the values you're interested in. The matches are separated with '>>'; the matches of interest are of the form 'name: parser'. */
+/* For more complicated expressions, capturing the results of all the parsers leads to untidy types. Since parsers are functions returning 'Result', we can simply call them one after another and use '?' to bail out on the first failure. Each step hands back the remaining input, which we feed into the next step; shadowing 'input' each time keeps this tidy. The old macro API needed 'do_parse!' for this. */
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 struct Point {
     x: f64,
     y: f64
 }
 
-named!(pointf<Point>,
-    do_parse!(
-        first: float64 >>
-        tag_s!(",") >>
-        second: float64
-        >>
-        (Point{x: first, y: second})
-    )
-);
+fn pointf(input: &str) -> IResult<&str,Point> {
+    let (input, first) = float64(input)?;
+    let (input, _) = tag(",").parse(input)?;
+    let (input, second) = float64(input)?;
+    Ok((input, Point{x: first, y: second}))
+}
+
+println!("got {:?}", pointf("20,52.2").finish().unwrap().1);
+// got Point { x: 20.0, y: 52.2 }
+
+assert_eq!(pointf("20,52.2"), Ok(("", Point{x: 20.0, y: 52.2})));
 
-println!("got {:?}", nom_res!(pointf,"20,52.2").unwrap());
-// got Point { x: 20, y: 52.2 }
+/* We're not interested in that tag's value (it can only be a comma) but we assign the two float values to temporary values which are used to build a struct. The code at the end can be any Rust expression. This particular shape is common enough that Nom has a combinator for it; 'separated_pair' keeps the outer two values and throws away the separator. */
 
-/* We're not interested in that tag's value (it can only be a comma) but we assign the two float values to temporary values which are used to build a struct. The code at the end can be any Rust expression. */
\ No newline at end of file
+use nom::sequence::separated_pair;
+
+fn pointf(input: &str) -> IResult<&str,Point> {
+    map(
+        separated_pair(float64, tag(","), float64),
+        |(x, y)| Point{x: x, y: y}
+    ).parse(input)
+}
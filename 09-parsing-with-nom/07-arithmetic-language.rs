@@ -0,0 +1,229 @@
+/* The arithmetic parser is a nice party trick, but people quickly want more from a calculator. Someone will write '-x * 2' and expect it to work, so we need unary minus, variables, powers, functions like 'sqrt' and 'max(a, b)' and a way to give a name to an intermediate result. Let's grow 'expr' into a small expression language. The grammar now has a few more levels, each one binding tighter than the one before it:
+
+expr   := 'let' name '=' expr 'in' expr | sum
+sum    := term (('+' | '-') term)*
+term   := unary (('*' | '/') unary)*
+unary  := '-' unary | power
+power  := atom ('^' unary)?
+atom   := number | name '(' args ')' | name | '(' expr ')'
+
+'^' is right associative, so '2^3^2' is '2^(3^2)', and it binds tighter than unary minus, so '-2^2' is -4 as it is in mathematics. Because 'power' asks for a 'unary' on the right hand side, '2^-1' also works.
+
+Variables have to be looked up somewhere. The caller supplies an environment, which is just a map from names to values. */
+
+use std::collections::HashMap;
+
+type Env = HashMap<String,f64>;
+
+/* Our parsers now need an extra argument. A Nom parser must take just the input, but that's no problem; a closure like '|i| expr(i, env)' is a parser which captures the environment and passes it along. */
+
+use nom::{IResult, Parser, Finish};
+use nom::bytes::complete::{tag, take_while, take_while1};
+use nom::branch::alt;
+use nom::sequence::{delimited, pair, preceded, terminated};
+use nom::character::complete::{digit1, satisfy};
+use nom::combinator::{opt, map, map_res, recognize, verify, not};
+use nom::multi::{fold_many0, separated_list0};
+use nom::error::{Error, ErrorKind};
+use std::str::FromStr;
+
+// 'ws' is the whitespace-skipping combinator from the first section
+
+/* Numbers are now unsigned. 'float64' accepts a leading sign, which would fight with unary minus: '-2^2' would be read as '(-2)^2'. The sign is now the job of 'unary'. */
+
+fn number(input: &str) -> IResult<&str,f64> {
+    map_res(
+        recognize((
+            digit1,
+            opt(pair(tag("."), digit1)),
+            opt((
+                alt((tag("e"), tag("E"))),
+                opt(alt((tag("+"), tag("-")))),
+                digit1
+            ))
+        )),
+        FromStr::from_str
+    ).parse(input)
+}
+
+// Names start with a letter or underscore; 'let' and 'in' are keywords and can't be used as names
+fn is_name_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn name(input: &str) -> IResult<&str,&str> {
+    verify(
+        recognize(pair(
+            take_while1(is_name_start),
+            take_while(is_name_char)
+        )),
+        |s: &str| s != "let" && s != "in"
+    ).parse(input)
+}
+
+// A keyword is a whole word, so 'inx' is a name and not 'in' followed by 'x'
+fn keyword<'a>(word: &'static str) -> impl Parser<&'a str, Output = &'a str, Error = Error<&'a str>> {
+    terminated(tag(word), not(satisfy(is_name_char)))
+}
+
+/* The built-in functions are matched on both their name and the number of arguments, so 'max(1)' is an error rather than a panic. Adding a function is a one-line change. */
+
+fn builtin(name: &str, args: &[f64]) -> Option<f64> {
+    match (name, args.len()) {
+        ("sin", 1) => Some(args[0].sin()),
+        ("cos", 1) => Some(args[0].cos()),
+        ("tan", 1) => Some(args[0].tan()),
+        ("sqrt", 1) => Some(args[0].sqrt()),
+        ("abs", 1) => Some(args[0].abs()),
+        ("ln", 1) => Some(args[0].ln()),
+        ("exp", 1) => Some(args[0].exp()),
+        ("max", 2) => Some(args[0].max(args[1])),
+        ("min", 2) => Some(args[0].min(args[1])),
+        _ => None
+    }
+}
+
+/* A function call and a variable both start with a name. We parse the name and the optional argument list, and '?' returns early if that fails, otherwise giving us the remaining input and the value. If the name is unknown we make our own error; 'Verify' is the error kind Nom uses when something parsed but wasn't acceptable. */
+
+fn named_value<'a>(input: &'a str, env: &Env) -> IResult<&'a str,f64> {
+    let (rest, (name, args)) = pair(
+        ws(name),
+        opt(delimited(
+            tag("("),
+            separated_list0(tag(","), |i| expr(i, env)),
+            ws(tag(")"))
+        ))
+    ).parse(input)?;
+    let value = match args {
+        Some(args) => builtin(name, &args),
+        None => env.get(name).cloned()
+    };
+    match value {
+        Some(v) => Ok((rest, v)),
+        None => Err(nom::Err::Error(Error::new(input, ErrorKind::Verify)))
+    }
+}
+
+fn atom<'a>(input: &'a str, env: &Env) -> IResult<&'a str,f64> {
+    alt((
+        ws(number),
+        |i| named_value(i, env),
+        ws(delimited(tag("("), |i| expr(i, env), tag(")")))
+    )).parse(input)
+}
+
+// The exponent is a 'unary', which in turn may be another power. That's what makes '^' right associative.
+fn power<'a>(input: &'a str, env: &Env) -> IResult<&'a str,f64> {
+    let (input, base) = atom(input, env)?;
+    let (input, exp) = opt(preceded(ws(tag("^")), |i| unary(i, env))).parse(input)?;
+    Ok((input, match exp {
+        Some(e) => base.powf(e),
+        None => base
+    }))
+}
+
+fn unary<'a>(input: &'a str, env: &Env) -> IResult<&'a str,f64> {
+    alt((
+        map(preceded(ws(tag("-")), |i| unary(i, env)), |v: f64| -v),
+        |i| power(i, env)
+    )).parse(input)
+}
+
+/* 'term' and 'sum' are the old 'term' and 'expr' with their operands going through the new levels. */
+
+fn term<'a>(input: &'a str, env: &Env) -> IResult<&'a str,f64> {
+    let (input, init) = unary(input, env)?;
+    fold_many0(
+        pair(
+            ws(alt((tag("*"), tag("/")))),
+            |i| unary(i, env)
+        ),
+        move || init,
+        |acc, v:(&str,f64)| {
+            if v.0 == "*" {acc * v.1} else {acc / v.1}
+        }
+    ).parse(input)
+}
+
+fn sum<'a>(input: &'a str, env: &Env) -> IResult<&'a str,f64> {
+    let (input, init) = term(input, env)?;
+    fold_many0(
+        pair(
+            ws(alt((tag("+"), tag("-")))),
+            |i| term(i, env)
+        ),
+        move || init,
+        |acc, v:(&str,f64)| {
+            if v.0 == "+" {acc + v.1} else {acc - v.1}
+        }
+    ).parse(input)
+}
+
+/* A 'let' binding evaluates its value, and then parses the body with a copy of the environment that contains the new name. The body is itself an 'expr', so bindings can be nested, and an inner binding hides an outer variable with the same name. Copying the map is not free, but formulas are small. The keywords go through 'keyword', which checks that no name character follows; otherwise 'let a = 1 inx' would be read as 'let a = 1 in x'. */
+
+fn let_in<'a>(input: &'a str, env: &Env) -> IResult<&'a str,f64> {
+    let (input, _) = ws(keyword("let")).parse(input)?;
+    let (input, name) = ws(name).parse(input)?;
+    let (input, _) = tag("=").parse(input)?;
+    let (input, value) = expr(input, env)?;
+    let (input, _) = ws(keyword("in")).parse(input)?;
+    let mut inner = env.clone();
+    inner.insert(name.to_string(), value);
+    expr(input, &inner)
+}
+
+fn expr<'a>(input: &'a str, env: &Env) -> IResult<&'a str,f64> {
+    alt((
+        |i| let_in(i, env),
+        |i| sum(i, env)
+    )).parse(input)
+}
+
+/* Finally a friendly entry point. All of the text must be used up, otherwise "2 3" would quietly evaluate to 2. */
+
+fn eval(text: &str, env: &Env) -> Result<f64,String> {
+    match expr(text, env).finish() {
+        Ok((rest, v)) => {
+            let rest = rest.trim();
+            if rest.is_empty() {
+                Ok(v)
+            } else {
+                Err(format!("unexpected '{}'", rest))
+            }
+        },
+        Err(e) => Err(format!("parse error {:?}", e.code))
+    }
+}
+
+fn main() {
+    let mut env = Env::new();
+    env.insert("x".to_string(), 3.0);
+    env.insert("pi".to_string(), std::f64::consts::PI);
+
+    println!("{:?}", eval("-x * 2", &env));
+    println!("{:?}", eval("2^3^2", &env));
+    println!("{:?}", eval("-2^2", &env));
+    println!("{:?}", eval("2^-1", &env));
+    println!("{:?}", eval("max(x, 10) - sqrt(16)", &env));
+    println!("{:?}", eval("let r = 2 in pi * r^2", &env));
+    println!("{:?}", eval("let a = 1 in let b = a + 1 in a * b", &env));
+    println!("{:?}", eval("let a = 1 inx", &env));
+    println!("{:?}", eval("2 3", &env));
+    println!("{:?}", eval("y + 1", &env));
+}
+// Ok(-6.0)
+// Ok(512.0)
+// Ok(-4.0)
+// Ok(0.5)
+// Ok(6.0)
+// Ok(12.566370614359172)
+// Ok(2.0)
+// Err("parse error Tag")
+// Err("unexpected '3'")
+// Err("parse error Tag")
+
+/* The last result is disappointing. 'named_value' did report an unknown variable, but when all of its alternatives fail 'alt' passes on the error from the last one, which was looking for '('. Our error is lost. The same happens inside the folds: in "2 + y" the fold stops at the '+', and we are told that "+ y" was unexpected. Parsing and evaluating at the same time mixes two kinds of failure together, and it also means we can't look at a formula without evaluating it. The fix for both is to have the parser build a tree, and to evaluate that tree afterwards. */
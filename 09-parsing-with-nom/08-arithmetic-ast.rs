@@ -0,0 +1,399 @@
+/* The expression language folds its input straight into an 'f64'. That is compact, but once the number comes out there is nothing left to look at; we can't print the formula, simplify it, or evaluate it again with different variables without parsing it again. The usual solution is for the parser to build an 'abstract syntax tree' (AST), and for evaluation to be a separate step which walks that tree.
+
+Rust enums are perfect for this. Each kind of expression is a variant, and the recursive variants 'Box' their children since an enum can't directly contain itself (it would have infinite size). The binary operators share a single variant with an 'Op' tag, which saves us writing five nearly identical cases everywhere. */
+
+use std::collections::HashMap;
+use std::fmt;
+
+type Env = HashMap<String,f64>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Add, Sub, Mul, Div, Pow
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Num(f64),
+    Var(String),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    Let(String, Box<Expr>, Box<Expr>)
+}
+
+use Expr::*;
+
+// Saves a lot of 'Box::new' noise
+fn binary(op: Op, left: Expr, right: Expr) -> Expr {
+    Binary(op, Box::new(left), Box::new(right))
+}
+
+/* The parsers have exactly the same shape as before, but now they build nodes instead of doing arithmetic. Because they don't need the environment any more, they are plain parser functions again and can be passed directly to combinators. The folds are particularly neat; the accumulator starts as the first operand, and each operator wraps it in a new 'Binary' node, so '1 - 2 - 3' becomes '(1 - 2) - 3' as it should. */
+
+use nom::{IResult, Parser, Finish};
+use nom::bytes::complete::tag;
+use nom::branch::alt;
+use nom::sequence::{delimited, pair, preceded, terminated};
+use nom::combinator::{opt, map, not};
+use nom::character::complete::satisfy;
+use nom::error::Error;
+use nom::multi::{many0, separated_list0};
+
+// 'ws', 'number', 'name' and 'keyword' are the same as before
+fn number(input: &str) -> IResult<&str,f64> { ... }
+fn name(input: &str) -> IResult<&str,&str> { ... }
+fn keyword<'a>(word: &'static str) -> impl Parser<&'a str, Output = &'a str, Error = Error<&'a str>> { ... }
+
+fn named_value(input: &str) -> IResult<&str,Expr> {
+    let (input, name) = ws(name).parse(input)?;
+    let (input, args) = opt(delimited(
+        tag("("),
+        separated_list0(tag(","), expr),
+        ws(tag(")"))
+    )).parse(input)?;
+    Ok((input, match args {
+        Some(args) => Call(name.to_string(), args),
+        None => Var(name.to_string())
+    }))
+}
+
+// An enum variant with a single field can be used as a function, so 'map(..., Num)' works
+fn atom(input: &str) -> IResult<&str,Expr> {
+    alt((
+        map(ws(number), Num),
+        named_value,
+        ws(delimited(tag("("), expr, tag(")")))
+    )).parse(input)
+}
+
+fn power(input: &str) -> IResult<&str,Expr> {
+    let (input, base) = atom(input)?;
+    let (input, exp) = opt(preceded(ws(tag("^")), unary)).parse(input)?;
+    Ok((input, match exp {
+        Some(e) => binary(Op::Pow, base, e),
+        None => base
+    }))
+}
+
+fn unary(input: &str) -> IResult<&str,Expr> {
+    alt((
+        map(preceded(ws(tag("-")), unary), |e| Neg(Box::new(e))),
+        power
+    )).parse(input)
+}
+
+/* The folds are different from before. 'fold_many0' wants a closure to make its initial value, and it may call that more than once, so it would have to clone our first operand. It's simpler to collect the operators and operands with 'many0' and then fold them with an ordinary iterator fold. */
+
+fn term(input: &str) -> IResult<&str,Expr> {
+    let (input, init) = unary(input)?;
+    let (input, rest) = many0(pair(
+        ws(alt((tag("*"), tag("/")))),
+        unary
+    )).parse(input)?;
+    Ok((input, rest.into_iter().fold(init, |acc, (op, v)| {
+        binary(if op == "*" {Op::Mul} else {Op::Div}, acc, v)
+    })))
+}
+
+fn sum(input: &str) -> IResult<&str,Expr> {
+    let (input, init) = term(input)?;
+    let (input, rest) = many0(pair(
+        ws(alt((tag("+"), tag("-")))),
+        term
+    )).parse(input)?;
+    Ok((input, rest.into_iter().fold(init, |acc, (op, v)| {
+        binary(if op == "+" {Op::Add} else {Op::Sub}, acc, v)
+    })))
+}
+
+fn let_in(input: &str) -> IResult<&str,Expr> {
+    let (input, _) = ws(keyword("let")).parse(input)?;
+    let (input, name) = ws(name).parse(input)?;
+    let (input, _) = tag("=").parse(input)?;
+    let (input, value) = expr(input)?;
+    let (input, _) = ws(keyword("in")).parse(input)?;
+    let (input, body) = expr(input)?;
+    Ok((input, Let(name.to_string(), Box::new(value), Box::new(body))))
+}
+
+fn expr(input: &str) -> IResult<&str,Expr> {
+    alt((let_in, sum)).parse(input)
+}
+
+fn parse(text: &str) -> Result<Expr,String> {
+    match expr(text).finish() {
+        Ok((rest, e)) => {
+            let rest = rest.trim();
+            if rest.is_empty() {
+                Ok(e)
+            } else {
+                Err(format!("unexpected '{}'", rest))
+            }
+        },
+        Err(e) => Err(format!("parse error {:?}", e.code))
+    }
+}
+
+let e = parse("2 * (x + 1) - -y^2").unwrap();
+println!("{:?}", e);
+// Binary(Sub, Binary(Mul, Num(2.0), Binary(Add, Var("x"), Num(1.0))), Neg(Binary(Pow, Var("y"), Num(2.0))))
+
+/* Evaluation is now a recursive method on 'Expr'. Unknown names are no longer parse errors, they are evaluation errors, and we know exactly which name was the problem. The question mark operator keeps the recursion tidy. 'builtin' now checks the number of arguments itself so it can give a useful message. */
+
+fn builtin(name: &str, args: &[f64]) -> Result<f64,String> {
+    let arity = match name {
+        "sin" | "cos" | "tan" | "sqrt" | "abs" | "ln" | "exp" => 1,
+        "max" | "min" => 2,
+        _ => return Err(format!("unknown function '{}'", name))
+    };
+    if args.len() != arity {
+        return Err(format!("'{}' expects {} argument(s), got {}", name, arity, args.len()));
+    }
+    Ok(match name {
+        "sin" => args[0].sin(),
+        "cos" => args[0].cos(),
+        "tan" => args[0].tan(),
+        "sqrt" => args[0].sqrt(),
+        "abs" => args[0].abs(),
+        "ln" => args[0].ln(),
+        "exp" => args[0].exp(),
+        "max" => args[0].max(args[1]),
+        _ => args[0].min(args[1])
+    })
+}
+
+impl Op {
+    fn apply(self, a: f64, b: f64) -> f64 {
+        match self {
+            Op::Add => a + b,
+            Op::Sub => a - b,
+            Op::Mul => a * b,
+            Op::Div => a / b,
+            Op::Pow => a.powf(b)
+        }
+    }
+}
+
+impl Expr {
+    fn eval(&self, env: &Env) -> Result<f64,String> {
+        match *self {
+            Num(v) => Ok(v),
+            Var(ref name) => env.get(name).cloned()
+                .ok_or_else(|| format!("unknown variable '{}'", name)),
+            Neg(ref e) => Ok(- e.eval(env)?),
+            Binary(op, ref left, ref right) => Ok(op.apply(left.eval(env)?, right.eval(env)?)),
+            Call(ref name, ref args) => {
+                // collecting into a 'Result' stops at the first error
+                let args = args.iter().map(|a| a.eval(env)).collect::<Result<Vec<_>,_>>()?;
+                builtin(name, &args)
+            },
+            Let(ref name, ref value, ref body) => {
+                let mut inner = env.clone();
+                inner.insert(name.clone(), value.eval(env)?);
+                body.eval(&inner)
+            }
+        }
+    }
+    ...
+}
+
+let mut env = Env::new();
+env.insert("x".to_string(), 3.0);
+println!("{:?}", parse("x * 2 + 1").unwrap().eval(&env));
+println!("{:?}", parse("x * 2 + y").unwrap().eval(&env));
+println!("{:?}", parse("max(1)").unwrap().eval(&env));
+// Ok(7.0)
+// Err("unknown variable 'y'")
+// Err("'max' expects 2 argument(s), got 1")
+
+/* Once we have a tree we can transform it. A constant-folding simplifier works from the bottom up: simplify the children first, and if they have all become numbers then do the arithmetic now. A few identities like 'x + 0' and 'x * 1' can also go, but notice that we don't touch 'x * 0'; if 'x' turns out to be infinity or NaN the answer isn't zero. A 'let' whose value is constant is removed by substituting the value into the body.
+
+Matching on a tuple of the operator and the two simplified children lets us express these rules as patterns. Several patterns can share an arm with '|', as long as they bind the same names. */
+
+impl Expr {
+    ...
+    fn simplify(&self) -> Expr {
+        match *self {
+            Num(_) | Var(_) => self.clone(),
+            Neg(ref e) => match e.simplify() {
+                Num(v) => Num(-v),
+                Neg(inner) => *inner,
+                e => Neg(Box::new(e))
+            },
+            Binary(op, ref left, ref right) => match (op, left.simplify(), right.simplify()) {
+                (op, Num(a), Num(b)) => Num(op.apply(a, b)),
+                (Op::Add, Num(z), e) | (Op::Add, e, Num(z)) | (Op::Sub, e, Num(z)) if z == 0.0 => e,
+                (Op::Mul, Num(one), e) | (Op::Mul, e, Num(one)) |
+                (Op::Div, e, Num(one)) | (Op::Pow, e, Num(one)) if one == 1.0 => e,
+                (op, left, right) => binary(op, left, right)
+            },
+            Call(ref name, ref args) => {
+                let args: Vec<_> = args.iter().map(|a| a.simplify()).collect();
+                let values: Vec<_> = args.iter()
+                    .filter_map(|a| match *a { Num(v) => Some(v), _ => None })
+                    .collect();
+                // Only fold calls that would succeed; a bad call is left for 'eval' to report
+                if values.len() == args.len() {
+                    if let Ok(v) = builtin(name, &values) {
+                        return Num(v);
+                    }
+                }
+                Call(name.clone(), args)
+            },
+            Let(ref name, ref value, ref body) => match value.simplify() {
+                Num(v) => body.substitute(name, v).simplify(),
+                value => Let(name.clone(), Box::new(value), Box::new(body.simplify()))
+            }
+        }
+    }
+
+    // Replace a variable with a value. An inner 'let' of the same name hides it, so we stop there.
+    fn substitute(&self, var: &str, v: f64) -> Expr {
+        match *self {
+            Var(ref name) if name == var => Num(v),
+            Num(_) | Var(_) => self.clone(),
+            Neg(ref e) => Neg(Box::new(e.substitute(var, v))),
+            Binary(op, ref left, ref right) => binary(op, left.substitute(var, v), right.substitute(var, v)),
+            Call(ref name, ref args) => Call(name.clone(), args.iter().map(|a| a.substitute(var, v)).collect()),
+            Let(ref name, ref value, ref body) => {
+                let body = if name == var {body.as_ref().clone()} else {body.substitute(var, v)};
+                Let(name.clone(), Box::new(value.substitute(var, v)), Box::new(body))
+            }
+        }
+    }
+}
+
+for s in &["2 * 3 + x", "x * (4 - 3) + 0", "let r = 2 in 3.5 * r^2 + x", "sqrt(16) * max(x, 2 + 3)"] {
+    println!("{} => {:?}", s, parse(s).unwrap().simplify());
+}
+// 2 * 3 + x => Binary(Add, Num(6.0), Var("x"))
+// x * (4 - 3) + 0 => Var("x")
+// let r = 2 in 3.5 * r^2 + x => Binary(Add, Num(14.0), Var("x"))
+// sqrt(16) * max(x, 2 + 3) => Binary(Mul, Num(4.0), Call("max", [Var("x"), Num(5.0)]))
+
+/* Those debug dumps are hard to read, so let's implement 'Display'. The trick to printing with as few parentheses as possible is to give each node a precedence that matches the grammar levels, from 'let' (lowest) up to numbers, names and calls (highest). A child needs parentheses if it binds more loosely than its parent. For the left-associative operators, the right operand also needs them when it binds equally loosely, because '1 - (2 - 3)' is not '1 - 2 - 3'. '^' is the other way round. The simplifier can produce negative numbers, and these print like a unary minus, so they get its precedence. */
+
+impl Expr {
+    ...
+    fn precedence(&self) -> u8 {
+        match *self {
+            Let(..) => 0,
+            Binary(Op::Add, ..) | Binary(Op::Sub, ..) => 1,
+            Binary(Op::Mul, ..) | Binary(Op::Div, ..) => 2,
+            Neg(_) => 3,
+            Num(v) if v < 0.0 => 3,
+            Binary(Op::Pow, ..) => 4,
+            Num(_) | Var(_) | Call(..) => 5
+        }
+    }
+}
+
+fn write_operand(f: &mut fmt::Formatter, e: &Expr, parens: bool) -> fmt::Result {
+    if parens {
+        write!(f, "({})", e)
+    } else {
+        write!(f, "{}", e)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Num(v) => write!(f, "{}", v),
+            Var(ref name) => write!(f, "{}", name),
+            Neg(ref e) => {
+                write!(f, "-")?;
+                write_operand(f, e, e.precedence() < 3)
+            },
+            Binary(op, ref left, ref right) => {
+                let prec = self.precedence();
+                let (sym, left_parens, right_parens) = match op {
+                    Op::Add => (" + ", left.precedence() < prec, right.precedence() <= prec),
+                    Op::Sub => (" - ", left.precedence() < prec, right.precedence() <= prec),
+                    Op::Mul => (" * ", left.precedence() < prec, right.precedence() <= prec),
+                    Op::Div => (" / ", left.precedence() < prec, right.precedence() <= prec),
+                    // the exponent is parsed as a 'unary', so only 'let' and the binary operators need wrapping
+                    Op::Pow => ("^", left.precedence() <= prec, right.precedence() < 3)
+                };
+                write_operand(f, left, left_parens)?;
+                write!(f, "{}", sym)?;
+                write_operand(f, right, right_parens)
+            },
+            Call(ref name, ref args) => {
+                write!(f, "{}(", name)?;
+                for (i, a) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", a)?;
+                }
+                write!(f, ")")
+            },
+            Let(ref name, ref value, ref body) => write!(f, "let {} = {} in {}", name, value, body)
+        }
+    }
+}
+
+for s in &["(1 - 2) - 3", "1 - (2 - 3)", "(2^3)^2", "2^(3^2)", "(-2)^2", "-(2^2)", "(let a = 1 in a) + 1"] {
+    println!("{} => {}", s, parse(s).unwrap());
+}
+// (1 - 2) - 3 => 1 - 2 - 3
+// 1 - (2 - 3) => 1 - (2 - 3)
+// (2^3)^2 => (2^3)^2
+// 2^(3^2) => 2^3^2
+// (-2)^2 => (-2)^2
+// -(2^2) => -2^2
+// (let a = 1 in a) + 1 => (let a = 1 in a) + 1
+
+println!("{}", parse("let r = 2 in 3.5 * r^2 + x").unwrap().simplify());
+// 14 + x
+
+/* How do we know that the printer and the parser agree? Trying a few cases by hand is not very convincing, since the interesting bugs hide in combinations we didn't think of. A property test generates lots of random trees and checks something that must always be true: printing a tree and parsing the result gives back the same tree.
+
+There are crates for this ('quickcheck' and 'proptest') but a tiny random number generator is enough here. 'xorshift' is not a good generator for anything serious, but it is fast, needs no dependencies, and with a fixed seed every run tests the same trees, so a failure can be reproduced. The generated trees use only things the parser can produce; numbers are never negative, for example, since '-2' parses as 'Neg(Num(2.0))'. */
+
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self, n: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % n
+    }
+}
+
+fn random_expr(rng: &mut Rng, depth: u32) -> Expr {
+    let names = ["x", "y", "rate"];
+    let leaf = depth == 0;
+    match rng.next(if leaf {2} else {7}) {
+        0 => Num(rng.next(1000) as f64 / 8.0),
+        1 => Var(names[rng.next(3) as usize].to_string()),
+        2 => Neg(Box::new(random_expr(rng, depth - 1))),
+        3 | 4 => {
+            let ops = [Op::Add, Op::Sub, Op::Mul, Op::Div, Op::Pow];
+            binary(ops[rng.next(5) as usize], random_expr(rng, depth - 1), random_expr(rng, depth - 1))
+        },
+        5 => {
+            let nargs = rng.next(3) as usize;
+            Call("max".to_string(), (0..nargs).map(|_| random_expr(rng, depth - 1)).collect())
+        },
+        _ => Let(names[rng.next(3) as usize].to_string(),
+            Box::new(random_expr(rng, depth - 1)), Box::new(random_expr(rng, depth - 1)))
+    }
+}
+
+let mut rng = Rng(0x2545F4914F6CDD1D);
+for _ in 0..2000 {
+    let e = random_expr(&mut rng, 5);
+    let text = e.to_string();
+    let back = parse(&text).expect(&text);
+    assert_eq!(e, back, "{}", text);
+}
+// and text which isn't an expression mustn't turn into one
+for text in ["let a = 1 inx", "letx = 1 in x"] {
+    assert!(parse(text).is_err(), "{}", text);
+}
+
+/* A mistake in the precedence rules, such as printing '(-2)^2' as '-2^2', shows up here straight away as two different trees. Tests like this are cheap to write once you have an AST. */
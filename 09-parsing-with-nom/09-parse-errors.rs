@@ -0,0 +1,393 @@
+/* So far our error messages have been things like 'Error(Error { input: "x120", code: Digit })' or "unexpected '* (3 +'". That's fine while you're writing the parser, but a user who typed '2 * (3 + ' wants to be told where the problem is and what was expected there. A good parse error needs three things: the line and column, what the parser was hoping to find, and what it found instead. With those we can print the kind of diagnostic that 'rustc' gives us, with a caret under the offending spot.
+
+Let's start with a type that all of our parsers can report through. It keeps a copy of the source line so that it can be displayed long after the input has gone. Nom already has a trait called 'ParseError', so ours is called 'SyntaxError'. */
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+struct SyntaxError {
+    line: usize,
+    column: usize,
+    expected: Vec<String>,
+    found: String,
+    source_line: String
+}
+
+/* The parsers work with the remaining input, so the position is the difference between the length of the source and the length of what's left. Lines are counted from one, like an editor does. The column counts characters, not bytes; otherwise the caret would drift to the right on any line containing 'é'. */
+
+impl SyntaxError {
+    fn new(source: &str, rest: &str, expected: Vec<String>) -> SyntaxError {
+        let offset = source.len() - rest.len();
+        let before = &source[..offset];
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line_end = source[offset..].find('\n').map(|i| offset + i).unwrap_or(source.len());
+        SyntaxError {
+            line: before.matches('\n').count() + 1,
+            column: source[line_start..offset].chars().count() + 1,
+            expected: expected,
+            found: describe(rest),
+            source_line: source[line_start..line_end].to_string()
+        }
+    }
+}
+
+// A short description of what is at this position: a whole word or number, or a single character
+fn describe(rest: &str) -> String {
+    match rest.chars().next() {
+        None => "end of input".to_string(),
+        Some(c) if c.is_alphanumeric() || c == '_' => {
+            let end = rest.find(|c: char| ! (c.is_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            format!("'{}'", &rest[..end])
+        },
+        Some(c) => format!("'{}'", c)
+    }
+}
+
+// "a", "a or b", "a, b or c"
+fn one_of(items: &[String]) -> String {
+    match items.split_last() {
+        None => "nothing".to_string(),
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last)
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // the gutter must be as wide as the line number
+        let margin = " ".repeat(self.line.to_string().len());
+        writeln!(f, "error: expected {}, found {}", one_of(&self.expected), self.found)?;
+        writeln!(f, "{}--> line {}, column {}", margin, self.line, self.column)?;
+        writeln!(f, "{} |", margin)?;
+        writeln!(f, "{} | {}", self.line, self.source_line)?;
+        // keep any tabs, so that the caret lines up however wide they are
+        let indent: String = self.source_line.chars().take(self.column - 1)
+            .map(|c| if c == '\t' {'\t'} else {' '})
+            .collect();
+        write!(f, "{} | {}^", margin, indent)
+    }
+}
+
+/* Now the hard part: where did parsing actually stop? Nom is a backtracking parser. When 'alt' tries an alternative and it fails, the next alternative is tried, and when 'many0' can't match another operator it just stops, successfully. So by the time '2 * (3 + ' gets back to us, the parser has happily matched the '2' and all we know is that the rest wasn't used.
+
+Nom gives us two tools for this. The first is that the error type is a type parameter; 'IResult<I,O>' is short for 'IResult<I,O,nom::error::Error<I>>', and we can put our own type there as long as it implements 'nom::error::ParseError'. That trait has a method 'or', which 'alt' calls to combine the errors when all of its alternatives fail. The default keeps the last error, but ours keeps the one that got furthest into the input, since the mistake is almost always there. If they got equally far, then either alternative would have done, so we keep both sets of labels.
+
+Our error only needs the remaining input where it happened, and labels saying what we expected there. */
+
+use nom::{IResult, Parser};
+use nom::bytes::complete::tag;
+use nom::branch::alt;
+use nom::sequence::{delimited, pair, preceded, terminated};
+use nom::character::complete::{digit1, alpha1, satisfy};
+use nom::combinator::{opt, map, map_res, recognize, cut, not};
+use nom::multi::{many0, separated_list1};
+use nom::error::{ErrorKind, FromExternalError};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Expected<'a> {
+    input: &'a str,
+    labels: Vec<&'static str>
+}
+
+impl <'a> nom::error::ParseError<&'a str> for Expected<'a> {
+    fn from_error_kind(input: &'a str, _kind: ErrorKind) -> Self {
+        Expected{input: input, labels: Vec::new()}
+    }
+
+    fn append(_input: &'a str, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    fn or(mut self, other: Self) -> Self {
+        if self.input.len() < other.input.len() {
+            self
+        } else if other.input.len() < self.input.len() {
+            other
+        } else {
+            for label in other.labels {
+                if ! self.labels.contains(&label) {
+                    self.labels.push(label);
+                }
+            }
+            self
+        }
+    }
+}
+
+// 'map_res' needs to be able to turn a failed conversion into our error type
+impl <'a,E> FromExternalError<&'a str,E> for Expected<'a> {
+    fn from_external_error(input: &'a str, _kind: ErrorKind, _e: E) -> Self {
+        Expected{input: input, labels: Vec::new()}
+    }
+}
+
+// Writing out the error type on every parser gets tiresome
+type PResult<'a,O> = IResult<&'a str,O,Expected<'a>>;
+
+/* Labels are added by our own combinator. 'expect' runs a parser, and if that fails without getting any further than where it started, replaces the error with one that says what we expected at this position. If it failed somewhere further in, that error is more specific and is passed up untouched. A closure of the right shape is a parser, so 'expect' can simply return one. 'nom::Err' has a handy 'map' method for changing the error inside an 'Error' or a 'Failure'. */
+
+fn expect<'a,O,F>(label: &'static str, mut parser: F) -> impl Parser<&'a str, Output = O, Error = Expected<'a>>
+where F: Parser<&'a str, Output = O, Error = Expected<'a>> {
+    move |input: &'a str| parser.parse(input).map_err(|err| err.map(|e| {
+        let input = input.trim_start();
+        if e.input.len() < input.len() && ! e.labels.is_empty() {
+            e
+        } else {
+            Expected{input: input, labels: vec![label]}
+        }
+    }))
+}
+
+/* The second tool is 'cut'. Some points in the grammar are points of no return. Once we have seen '2 *', there must be an operand next; if there isn't, that's an error, not a reason to try something else. 'cut' turns an 'Error' into a 'Failure', and 'alt', 'many0' and 'opt' pass a 'Failure' straight up instead of backtracking. The same goes for a ')' after '(' and an expression, for an argument after the '(' or a ',' of a call (unless the call is empty, as in 'max()'), and for everything after the 'let' keyword. 'keyword' makes sure that 'let' is a whole word, so a variable called 'letter' still works.
+
+The labels are for humans. The grammar tries five different operators after an operand, but a 'unary' is where any operand can start, so that is where we expect "an expression". Here are the parsers from the AST version with their labels and cuts added. They all return 'PResult' now, including 'number' and 'name', since every parser in a chain must agree on the error type. */
+
+fn keyword<'a>(word: &'static str) -> impl Parser<&'a str, Output = &'a str, Error = Expected<'a>> {
+    terminated(tag(word), not(satisfy(is_name_char)))
+}
+
+fn number(input: &str) -> PResult<'_,f64> { ... }
+fn name(input: &str) -> PResult<'_,&str> { ... }
+
+fn named_value(input: &str) -> PResult<'_,Expr> {
+    let (input, name) = ws(name).parse(input)?;
+    let (input, args) = opt(preceded(
+        tag("("),
+        alt((
+            map(ws(tag(")")), |_| Vec::new()),
+            terminated(
+                separated_list1(tag(","), cut(expr)),
+                ws(cut(expect("',' or ')'", tag(")"))))
+            )
+        ))
+    )).parse(input)?;
+    Ok((input, match args {
+        Some(args) => Call(name.to_string(), args),
+        None => Var(name.to_string())
+    }))
+}
+
+fn atom(input: &str) -> PResult<'_,Expr> {
+    alt((
+        map(ws(number), Num),
+        named_value,
+        ws(delimited(tag("("), expr, cut(expect("')'", tag(")")))))
+    )).parse(input)
+}
+
+fn power(input: &str) -> PResult<'_,Expr> {
+    let (input, base) = atom(input)?;
+    let (input, exp) = opt(preceded(ws(tag("^")), cut(unary))).parse(input)?;
+    Ok((input, match exp {
+        Some(e) => binary(Op::Pow, base, e),
+        None => base
+    }))
+}
+
+fn unary(input: &str) -> PResult<'_,Expr> {
+    expect("an expression", alt((
+        map(preceded(ws(tag("-")), unary), |e| Neg(Box::new(e))),
+        power
+    ))).parse(input)
+}
+
+fn term(input: &str) -> PResult<'_,Expr> {
+    let (input, init) = unary(input)?;
+    let (input, rest) = many0(pair(
+        ws(alt((tag("*"), tag("/")))),
+        cut(unary)
+    )).parse(input)?;
+    Ok((input, rest.into_iter().fold(init, |acc, (op, v)| {
+        binary(if op == "*" {Op::Mul} else {Op::Div}, acc, v)
+    })))
+}
+
+fn sum(input: &str) -> PResult<'_,Expr> {
+    let (input, init) = term(input)?;
+    let (input, rest) = many0(pair(
+        ws(alt((tag("+"), tag("-")))),
+        cut(term)
+    )).parse(input)?;
+    Ok((input, rest.into_iter().fold(init, |acc, (op, v)| {
+        binary(if op == "+" {Op::Add} else {Op::Sub}, acc, v)
+    })))
+}
+
+fn let_in(input: &str) -> PResult<'_,Expr> {
+    let (input, _) = ws(keyword("let")).parse(input)?;
+    cut(let_body).parse(input)
+}
+
+fn let_body(input: &str) -> PResult<'_,Expr> {
+    let (input, name) = ws(expect("a name", name)).parse(input)?;
+    let (input, _) = expect("'='", tag("=")).parse(input)?;
+    let (input, value) = expr(input)?;
+    let (input, _) = ws(expect("'in'", keyword("in"))).parse(input)?;
+    let (input, body) = expr(input)?;
+    Ok((input, Let(name.to_string(), Box::new(value), Box::new(body))))
+}
+
+fn expr(input: &str) -> PResult<'_,Expr> {
+    alt((let_in, sum)).parse(input)
+}
+
+/* 'parse_all' is the one entry point for every parser. It runs the parser and insists that all the input is used; if there's something left over, then "end of input" is what we expected at that point. A parser that failed without any labels is still reported at the right place. It's generic over the parser, so it works for the greetings and numbers as well as for 'expr'. */
+
+fn parse_all<'a,O,F>(mut parser: F, text: &'a str) -> Result<O,SyntaxError>
+where F: Parser<&'a str, Output = O, Error = Expected<'a>> {
+    let e = match parser.parse(text) {
+        Ok((rest, v)) => {
+            if rest.trim().is_empty() {
+                return Ok(v);
+            }
+            Expected{input: rest.trim_start(), labels: vec!["end of input"]}
+        },
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => e,
+        Err(nom::Err::Incomplete(_)) => Expected{input: "", labels: vec!["more input"]}
+    };
+    let mut expected: Vec<_> = e.labels.iter().map(|s| s.to_string()).collect();
+    if expected.is_empty() {
+        expected.push("valid input".to_string());
+    }
+    Err(SyntaxError::new(text, e.input, expected))
+}
+
+match parse_all(expr, "2 * (3 + ") {
+    Ok(e) => println!("{}", e),
+    Err(e) => println!("{}", e)
+}
+/*
+error: expected an expression, found end of input
+ --> line 1, column 10
+  |
+1 | 2 * (3 +
+  |          ^
+*/
+
+// Some more mistakes
+for s in &["2 * (3 + 4", "2 3", "max(1 2)", "max(1,)", "max(", "let x 2 in x", "1 +\n\t2 * $"] {
+    println!("{}\n", parse_all(expr, s).unwrap_err());
+}
+/*
+error: expected ')', found end of input
+ --> line 1, column 11
+  |
+1 | 2 * (3 + 4
+  |           ^
+
+error: expected end of input, found '3'
+ --> line 1, column 3
+  |
+1 | 2 3
+  |   ^
+
+error: expected ',' or ')', found '2'
+ --> line 1, column 7
+  |
+1 | max(1 2)
+  |       ^
+
+error: expected an expression, found ')'
+ --> line 1, column 7
+  |
+1 | max(1,)
+  |       ^
+
+error: expected an expression, found end of input
+ --> line 1, column 5
+  |
+1 | max(
+  |     ^
+
+error: expected '=', found '2'
+ --> line 1, column 7
+  |
+1 | let x 2 in x
+  |       ^
+
+error: expected an expression, found '$'
+ --> line 2, column 6
+  |
+2 | 	2 * $
+  | 	    ^
+*/
+
+/* In the last example the error is on the second line, and the gutter shows which one. That line starts with a tab, which is as wide as the terminal likes, so the line under it copies the tab rather than counting it as one space. Because 'cut' stops the search at the first point of no return, each error is reported where it happened rather than where the parser finally gave up.
+
+The earlier parsers can report through the same type by changing their return type and labelling their pieces. 'int8' now uses 'map_res' so that "1200" is a failure of the parser rather than an 'Ok' holding an 'Err'. A '.' in a number must be followed by digits, so that is another place for 'cut'. */
+
+fn get_greeting(input: &str) -> PResult<'_,&str> {
+    ws(alt((expect("'hi'", tag("hi")), expect("'bye'", tag("bye"))))).parse(input)
+}
+
+fn full_greeting(input: &str) -> PResult<'_,(&str,Option<&str>)> {
+    pair(
+        get_greeting,
+        opt(expect("a name", alpha1))
+    ).parse(input)
+}
+
+fn int8(input: &str) -> PResult<'_,i8> {
+    expect("an 8-bit integer", map_res(digit1, FromStr::from_str)).parse(input)
+}
+
+fn signed_digits(input: &str) -> PResult<'_,(Option<&str>,&str)> {
+    pair(
+        opt(alt((tag("+"), tag("-")))),
+        expect("digits", digit1)
+    ).parse(input)
+}
+
+fn maybe_signed_digits(input: &str) -> PResult<'_,&str> {
+    recognize(signed_digits).parse(input)
+}
+
+fn floating_point(input: &str) -> PResult<'_,&str> {
+    recognize(
+        (
+            maybe_signed_digits,
+            opt(pair(
+                tag("."),
+                cut(expect("digits", digit1))
+            )),
+            opt(pair(
+                alt((tag("e"), tag("E"))),
+                maybe_signed_digits
+            ))
+        )
+    ).parse(input)
+}
+
+println!("{:?}", parse_all(full_greeting, " hi Bob  "));
+println!("{:?}", parse_all(int8, "120"));
+println!("{}", parse_all(get_greeting, "  hola ").unwrap_err());
+println!("{}", parse_all(full_greeting, " hi Bob!").unwrap_err());
+println!("{}", parse_all(int8, "1200").unwrap_err());
+println!("{}", parse_all(floating_point, "2.e6").unwrap_err());
+/*
+Ok(("hi", Some("Bob")))
+Ok(120)
+error: expected 'hi' or 'bye', found 'hola'
+ --> line 1, column 3
+  |
+1 |   hola
+  |   ^
+error: expected end of input, found '!'
+ --> line 1, column 8
+  |
+1 |  hi Bob!
+  |        ^
+error: expected an 8-bit integer, found '1200'
+ --> line 1, column 1
+  |
+1 | 1200
+  | ^
+error: expected digits, found 'e6'
+ --> line 1, column 3
+  |
+1 | 2.e6
+  |   ^
+
+The greeting error lists both 'hi' and 'bye' because 'alt' asked our 'or' to combine two errors at the same position. 'parse_all' returns a plain 'Result', so these errors work with '?' like any other, and because 'SyntaxError' implements 'Display' it is easy to make it implement 'std::error::Error' as well. */
@@ -0,0 +1,218 @@
+/* The number parsers in the fourth section only understand plain decimal numbers, and 'int8' hands back a 'Result' inside the parser's 'IResult', so there are two different ways to fail and the caller has to check both. Rust's own numeric literals have a lot more to them. They can be written in hexadecimal, octal or binary with '0x', '0o' and '0b', digits may be separated with '_' for readability, and a suffix gives the type: '13i32', '1.3f64', '0xffu8'. Let's write a parser for the whole lot, which returns a properly typed value and treats a number that doesn't fit into its type as an error like any other.
+
+The result is an enum with a variant for each type we support. */
+
+use nom::{IResult, Parser, Finish};
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while, take_while1};
+use nom::character::complete::{one_of, satisfy};
+use nom::combinator::{opt, map, recognize, verify, all_consuming};
+use nom::sequence::{pair, preceded};
+use nom::error::{ErrorKind, ParseError};
+use std::convert::TryFrom;
+use std::num::IntErrorKind;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Literal {
+    I8(i8), I16(i16), I32(i32), I64(i64),
+    U8(u8), U16(u16), U32(u32), U64(u64),
+    F32(f32), F64(f64)
+}
+
+/* Nom's default error only knows what kind of parser failed, which is no help in explaining that '256u8' is too big. So we make our own error type, as in the last section. There are several ways for a literal to be wrong. */
+
+#[derive(Debug, Clone, PartialEq)]
+enum LiteralError<'a> {
+    // not a number at all; where it went wrong, and the nom parser that gave up
+    Syntax(&'a str, ErrorKind),
+    // the literal, and the type it doesn't fit into
+    OutOfRange(&'a str, &'a str),
+    // the literal, and its radix
+    InvalidDigit(&'a str, u32),
+    // the literal, and the suffix that can't be used with it
+    BadSuffix(&'a str, &'a str)
+}
+
+impl <'a> ParseError<&'a str> for LiteralError<'a> {
+    fn from_error_kind(input: &'a str, kind: ErrorKind) -> Self {
+        LiteralError::Syntax(input, kind)
+    }
+
+    fn append(_input: &'a str, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+type LResult<'a,O> = IResult<&'a str,O,LiteralError<'a>>;
+
+/* Decimal digits must start with a digit, because '_1' is a perfectly good variable name. After that, underscores can go anywhere. With a prefix there's no such worry, and even '0x_ff' is allowed, as long as there is at least one real digit.
+
+Notice that octal and binary digits are parsed as any decimal digit. If we stopped at the first digit that doesn't belong, then '0b102' would be read as '0b10' followed by a mysterious suffix '2'; it's much kinder to say that '2' isn't a binary digit. */
+
+fn decimal_digits(input: &str) -> LResult<'_,&str> {
+    recognize(pair(
+        satisfy(|c| c.is_ascii_digit()),
+        take_while(|c: char| c.is_ascii_digit() || c == '_')
+    )).parse(input)
+}
+
+fn radix_digits<'a>(radix: u32) -> impl Parser<&'a str, Output = &'a str, Error = LiteralError<'a>> {
+    verify(
+        take_while1(move |c: char| if radix == 16 {c.is_ascii_hexdigit()} else {c.is_ascii_digit()} || c == '_'),
+        |s: &str| s.chars().any(|c| c != '_')
+    )
+}
+
+// This is 'floating_point' again, but without the sign and with underscores
+fn decimal(input: &str) -> LResult<'_,&str> {
+    recognize((
+        decimal_digits,
+        opt(pair(tag("."), decimal_digits)),
+        opt((one_of("eE"), opt(one_of("+-")), decimal_digits))
+    )).parse(input)
+}
+
+/* A literal is an optional sign, a number in one of the four radixes, and a suffix. The suffix is simply whatever letters and digits follow the number, so '12px' gets a suffix of 'px', which we can complain about by name. 'inf' and 'nan' are treated like decimal numbers, so they can have a sign and a suffix too. (Rust's literals don't have a sign; '-1' is the negation operator applied to '1'. But the earlier number parsers accepted a sign, and '-128i8' should work even though '128i8' doesn't fit.)
+
+The parser finds out what the literal looks like, and 'convert' decides what it means. A failed conversion is returned as 'nom::Err::Failure'; we know that this is a number, so there's no point in any 'alt' further up trying something else. */
+
+fn literal(input: &str) -> LResult<'_,Literal> {
+    let (rest, (sign, (radix, digits), suffix)) = (
+        opt(one_of("+-")),
+        alt((
+            map(preceded(tag("0x"), radix_digits(16)), |d| (16, d)),
+            map(preceded(tag("0o"), radix_digits(8)), |d| (8, d)),
+            map(preceded(tag("0b"), radix_digits(2)), |d| (2, d)),
+            map(alt((tag("inf"), tag("nan"), decimal)), |d| (10, d))
+        )),
+        take_while(|c: char| c.is_alphanumeric() || c == '_')
+    ).parse(input)?;
+    let text = &input[..input.len() - rest.len()];
+    match convert(sign == Some('-'), radix, digits, suffix, text) {
+        Ok(lit) => Ok((rest, lit)),
+        Err(e) => Err(nom::Err::Failure(e))
+    }
+}
+
+/* Without a suffix, Rust makes an integer literal an 'i32' and a floating-point literal an 'f64', and so do we. A float suffix on a decimal integer is fine ('13f32' is 13.0) but there's no such thing as a binary float, and an integer suffix on '1.5' is a mistake.
+
+Integers are first parsed as a 'u128', which is big enough for the magnitude of anything we support, and then the sign is applied. 'TryFrom' does the range check for each type; a little macro saves us writing out the same line eight times. Floats don't overflow in the same way, they just become infinite, so that's what we check for. */
+
+fn convert<'a>(negative: bool, radix: u32, digits: &str, suffix: &'a str, text: &'a str) -> Result<Literal,LiteralError<'a>> {
+    let digits = digits.replace('_', "");
+    let float = radix == 10 && digits.contains(&['.', 'e', 'E', 'i', 'n'][..]);
+    let suffix = match suffix {
+        "" if float => "f64",
+        "" => "i32",
+        s => s
+    };
+    let out_of_range = || LiteralError::OutOfRange(text, suffix);
+
+    if suffix == "f32" || suffix == "f64" {
+        if radix != 10 {
+            return Err(LiteralError::BadSuffix(text, suffix));
+        }
+        let digits = if negative {format!("-{}", digits)} else {digits};
+        // the grammar only lets through things that 'parse' understands
+        return if suffix == "f32" {
+            let v: f32 = digits.parse().unwrap();
+            if v.is_infinite() && ! digits.contains("inf") {Err(out_of_range())} else {Ok(Literal::F32(v))}
+        } else {
+            let v: f64 = digits.parse().unwrap();
+            if v.is_infinite() && ! digits.contains("inf") {Err(out_of_range())} else {Ok(Literal::F64(v))}
+        };
+    }
+
+    if float {
+        return Err(LiteralError::BadSuffix(text, suffix));
+    }
+    let magnitude = u128::from_str_radix(&digits, radix).map_err(|e| match *e.kind() {
+        IntErrorKind::PosOverflow => out_of_range(),
+        _ => LiteralError::InvalidDigit(text, radix)
+    })?;
+    let value = i128::try_from(magnitude).map_err(|_| out_of_range())?;
+    let value = if negative {-value} else {value};
+
+    macro_rules! int {
+        ($variant:ident, $t:ty) => (
+            Literal::$variant(<$t>::try_from(value).map_err(|_| out_of_range())?)
+        )
+    }
+
+    Ok(match suffix {
+        "i8" => int!(I8, i8),
+        "i16" => int!(I16, i16),
+        "i32" => int!(I32, i32),
+        "i64" => int!(I64, i64),
+        "u8" => int!(U8, u8),
+        "u16" => int!(U16, u16),
+        "u32" => int!(U32, u32),
+        "u64" => int!(U64, u64),
+        _ => return Err(LiteralError::BadSuffix(text, suffix))
+    })
+}
+
+// 'all_consuming' fails unless the parser used up all of the input, which is what we want for a single literal
+fn parse_literal(text: &str) -> Result<Literal,LiteralError<'_>> {
+    all_consuming(literal).parse(text).finish().map(|(_, lit)| lit)
+}
+
+for s in &["42", "-17", "1_000_000u64", "0xff", "0xFF_FFu16", "0o755", "0b1010_1010u8", "-128i8",
+           "13i32", "13f32", "1.3f64", "2.5e-3", "6.02e23f32", "-inf", "0x1f32"] {
+    println!("{:<14} {:?}", s, parse_literal(s));
+}
+/*
+42             Ok(I32(42))
+-17            Ok(I32(-17))
+1_000_000u64   Ok(U64(1000000))
+0xff           Ok(I32(255))
+0xFF_FFu16     Ok(U16(65535))
+0o755          Ok(I32(493))
+0b1010_1010u8  Ok(U8(170))
+-128i8         Ok(I8(-128))
+13i32          Ok(I32(13))
+13f32          Ok(F32(13.0))
+1.3f64         Ok(F64(1.3))
+2.5e-3         Ok(F64(0.0025))
+6.02e23f32     Ok(F32(6.02e23))
+-inf           Ok(F64(-inf))
+0x1f32         Ok(I32(7986))
+*/
+
+/* The last one is a classic gotcha, and Rust itself does the same thing; 'f' is a hexadecimal digit, so '0x1f32' is just a big hex number. Now for the failures: */
+
+for s in &["128i8", "256u8", "-1u32", "3000000000", "1e39f32", "0b102", "0o8",
+           "1.5i32", "0b1f32", "12px", "info", "x12", "1.2.3"] {
+    println!("{:<14} {:?}", s, parse_literal(s));
+}
+/*
+128i8          Err(OutOfRange("128i8", "i8"))
+256u8          Err(OutOfRange("256u8", "u8"))
+-1u32          Err(OutOfRange("-1u32", "u32"))
+3000000000     Err(OutOfRange("3000000000", "i32"))
+1e39f32        Err(OutOfRange("1e39f32", "f32"))
+0b102          Err(InvalidDigit("0b102", 2))
+0o8            Err(InvalidDigit("0o8", 8))
+1.5i32         Err(BadSuffix("1.5i32", "i32"))
+0b1f32         Err(BadSuffix("0b1f32", "f32"))
+12px           Err(BadSuffix("12px", "px"))
+info           Err(BadSuffix("info", "o"))
+x12            Err(Syntax("x12", Satisfy))
+1.2.3          Err(Syntax(".3", Eof))
+*/
+
+// The edges of the ranges work, and 'nan' is a number (although not equal to itself, so we can't use 'assert_eq!')
+assert_eq!(parse_literal("-9223372036854775808i64"), Ok(Literal::I64(i64::MIN)));
+assert_eq!(parse_literal("18446744073709551615u64"), Ok(Literal::U64(u64::MAX)));
+assert_eq!(parse_literal("0x_ff_u8"), Ok(Literal::U8(255)));
+match parse_literal("nan") {
+    Ok(Literal::F64(v)) => assert!(v.is_nan()),
+    other => panic!("expected nan, got {:?}", other)
+}
+
+/* Used as part of a bigger parser, 'literal' just stops where the number stops: */
+
+println!("{:?}", literal("0xffu8 + 1"));
+// Ok((" + 1", U8(255)))
+
+/* Compare that with 'int8' from before, which gave us 'Ok(("", Err(ParseIntError { kind: PosOverflow })))' for "1200". Here there is one 'Result' to check, and an error always says what was wrong with the literal. Putting the range checks in 'convert' rather than in the grammar keeps the parser simple; the grammar only decides what a number looks like. */
@@ -0,0 +1,237 @@
+/* Every parser so far has been handed the whole input as a '&str'. That's fine for a line typed by a user, but not for a multi-gigabyte file of points; we don't want to read it all into memory just to parse it. What we want is to read a chunk, parse as many complete items out of it as we can, throw those away, and read the next chunk. The trouble is the item that straddles two chunks. If a chunk ends with "52", is that the number 52, or the start of 52.2?
+
+This is what 'Incomplete' is for. As we saw with 'streaming_greeting', the parsers in the 'streaming' modules don't assume that the end of their input is the end of the data, and return 'Incomplete' instead. So the plan is to use streaming parsers, and whenever one of them says 'Incomplete', read some more and try again from the start of the item.
+
+The number and point parsers need no changes at all; we just import the streaming versions of 'tag', 'digit1' and friends. */
+
+use nom::{IResult, Parser};
+use nom::bytes::streaming::tag;
+use nom::branch::alt;
+use nom::character::streaming::{digit1, multispace0};
+use nom::combinator::{opt, recognize, map, map_res};
+use nom::sequence::{pair, preceded, separated_pair};
+use std::str::{self, FromStr};
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+
+fn signed_digits(input: &str) -> IResult<&str, (Option<&str>,&str)> {
+    pair(opt(alt((tag("+"), tag("-")))), digit1).parse(input)
+}
+
+fn floating_point(input: &str) -> IResult<&str,&str> {
+    recognize((
+        signed_digits,
+        opt(pair(tag("."), digit1)),
+        opt(pair(alt((tag("e"), tag("E"))), signed_digits))
+    )).parse(input)
+}
+
+fn float64(input: &str) -> IResult<&str,f64> {
+    map_res(floating_point, FromStr::from_str).parse(input)
+}
+
+#[derive(Debug, PartialEq)]
+struct Point {
+    x: f64,
+    y: f64
+}
+
+fn pointf(input: &str) -> IResult<&str,Point> {
+    map(
+        separated_pair(float64, tag(","), float64),
+        |(x, y)| Point{x: x, y: y}
+    ).parse(input)
+}
+
+/* The driver looks a lot like the 'Lines' reader from the file chapter; it wraps anything that implements 'BufRead' and keeps a buffer. Here the buffer holds text which has been read but not yet parsed, and 'start' is where the next item begins.
+
+'pending' is needed because we read bytes, not characters. A chunk can end in the middle of a multi-byte UTF-8 character, and the parsers work with '&str', so we only move bytes into the text buffer once we have the complete character. We also count lines as we go, so that errors can say where they happened. */
+
+struct Stream<R> {
+    reader: R,
+    buf: String,
+    start: usize,
+    pending: Vec<u8>,
+    line: usize,
+    eof: bool,
+    done: bool
+}
+
+impl <R: BufRead> Stream<R> {
+    fn new(reader: R) -> Stream<R> {
+        Stream{reader: reader, buf: String::new(), start: 0, pending: Vec::new(), line: 1, eof: false, done: false}
+    }
+
+    // Like 'Lines::next', this returns 'None' when there are no more items, and the item is wrapped in an 'io::Result'
+    fn next<O,F>(&mut self, mut parser: F) -> Option<io::Result<O>>
+    where F: FnMut(&str) -> IResult<&str,O> {
+        if self.done {
+            return None;
+        }
+        loop {
+            let input = &self.buf[self.start..];
+            match parser(input) {
+                Ok((rest, value)) => {
+                    let used = input.len() - rest.len();
+                    self.line += input[..used].matches('\n').count();
+                    self.start += used;
+                    return Some(Ok(value));
+                },
+                Err(nom::Err::Incomplete(_)) if ! self.eof => {
+                    if let Err(e) = self.read_more() {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                },
+                Err(nom::Err::Incomplete(_)) => {
+                    self.done = true;
+                    return if input.trim().is_empty() {
+                        None
+                    } else {
+                        Some(Err(self.error(io::ErrorKind::UnexpectedEof, "unexpected end of input")))
+                    };
+                },
+                Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                    // count the lines up to where the parser failed
+                    let at = input.len() - e.input.len();
+                    self.line += input[..at].matches('\n').count();
+                    self.done = true;
+                    return Some(Err(if self.eof && e.input.trim().is_empty() {
+                        self.error(io::ErrorKind::UnexpectedEof, "unexpected end of input")
+                    } else {
+                        self.error(io::ErrorKind::InvalidData, "parse error")
+                    }));
+                }
+            }
+        }
+    }
+    ...
+}
+
+/* Note that we don't keep going after an error. The parser would just fail at the same place again, forever.
+
+The interesting part is reading more. First the text which has already been parsed is thrown away, so the buffer only ever holds the current item and one chunk. 'fill_buf' hands us whatever the 'BufReader' has, without copying, and 'consume' tells it how much we used. 'from_utf8' tells us how many bytes make up valid UTF-8; if it only failed because the bytes ran out ('error_len' is 'None'), then the rest of the character is still to come.
+
+There is one last problem. At the very end of the data, a streaming parser still can't tell that "4" is a complete number, because it can't know that there is nothing more to come. So when the reader runs dry, we add a line feed. Our items are separated by whitespace anyway, and this gives the last one a proper ending. */
+
+fn read_more(&mut self) -> io::Result<()> {
+    // throw away what has already been parsed
+    self.buf.drain(..self.start);
+    self.start = 0;
+
+    let n = {
+        let chunk = self.reader.fill_buf()?;
+        self.pending.extend_from_slice(chunk);
+        chunk.len()
+    };
+    self.reader.consume(n);
+    if n == 0 {
+        if ! self.pending.is_empty() {
+            let line = self.unread_line(self.pending.len());
+            return Err(self.error_at(line, io::ErrorKind::InvalidData, "stream did not end with valid UTF-8"));
+        }
+        self.eof = true;
+        self.buf.push('\n');
+        return Ok(());
+    }
+
+    let valid = match str::from_utf8(&self.pending) {
+        Ok(s) => s.len(),
+        Err(e) => if e.error_len().is_some() {
+            let line = self.unread_line(e.valid_up_to());
+            return Err(self.error_at(line, io::ErrorKind::InvalidData, "stream did not contain valid UTF-8"));
+        } else {
+            e.valid_up_to()
+        }
+    };
+    self.buf.push_str(str::from_utf8(&self.pending[..valid]).unwrap());
+    self.pending.drain(..valid);
+    Ok(())
+}
+
+/* 'self.line' is the line of the item being parsed. A bad byte can be further on, since a chunk holds more than one item, so for those errors we count the newlines in the text which hasn't been parsed yet, and in the bytes of 'pending' before the bad one. */
+
+// The line of byte 'at' in 'pending', which comes after all the text not yet parsed
+fn unread_line(&self, at: usize) -> usize {
+    let newlines = self.pending[..at].iter().filter(|&&b| b == b'\n').count();
+    self.line + self.buf[self.start..].matches('\n').count() + newlines
+}
+
+fn error(&self, kind: io::ErrorKind, msg: &str) -> io::Error {
+    self.error_at(self.line, kind, msg)
+}
+
+fn error_at(&self, line: usize, kind: io::ErrorKind, msg: &str) -> io::Error {
+    io::Error::new(kind, format!("{} at line {}", msg, line))
+}
+
+/* An item parser must skip the whitespace in front of it. (Skipping whitespace after it would not work so well; at the end of a chunk, 'multispace0' can't know whether more spaces are coming, so every item would have to wait for the next one to start.)
+
+'&[u8]' implements 'Read', so we can test with a string. Giving the 'BufReader' a tiny capacity of four bytes makes sure that items are split between chunks. */
+
+fn point_item(input: &str) -> IResult<&str,Point> {
+    preceded(multispace0, pointf).parse(input)
+}
+
+let text = "20,52.2\n-1.5e3,0.25\n  3,4";
+let mut points = Stream::new(io::BufReader::with_capacity(4, text.as_bytes()));
+while let Some(p) = points.next(point_item) {
+    println!("{:?}", p);
+}
+// Ok(Point { x: 20.0, y: 52.2 })
+// Ok(Point { x: -1500.0, y: 0.25 })
+// Ok(Point { x: 3.0, y: 4.0 })
+
+let mut bad = Stream::new(io::BufReader::with_capacity(4, "1,2\n3;4\n".as_bytes()));
+while let Some(p) = bad.next(point_item) {
+    println!("{:?}", p);
+}
+// Ok(Point { x: 1.0, y: 2.0 })
+// Err(Custom { kind: InvalidData, error: "parse error at line 2" })
+
+let mut short = Stream::new("1,2\n3,".as_bytes());
+while let Some(p) = short.next(point_item) {
+    println!("{:?}", p);
+}
+// Ok(Point { x: 1.0, y: 2.0 })
+// Err(Custom { kind: UnexpectedEof, error: "unexpected end of input at line 2" })
+
+/* A bad byte is reported on its own line, whether it turns up in a later chunk or in the same chunk as the item before it. With a capacity of 64 the whole input is read at once, and the error comes before the first point has been parsed. */
+
+for cap in [4, 64] {
+    let mut bad_utf8 = Stream::new(io::BufReader::with_capacity(cap, &b"1,2\n3,\xff4\n5,6\n"[..]));
+    while let Some(p) = bad_utf8.next(point_item) {
+        println!("{:?}", p);
+    }
+}
+// Ok(Point { x: 1.0, y: 2.0 })
+// Err(Custom { kind: InvalidData, error: "stream did not contain valid UTF-8 at line 2" })
+// Err(Custom { kind: InvalidData, error: "stream did not contain valid UTF-8 at line 2" })
+
+/* What about 'fold_sum'? We can't use 'fold_many1' on a stream; it would keep on returning 'Incomplete' until it had seen the whole file, which is exactly what we are trying to avoid. Instead, the driver does the folding. The accumulator lives outside the buffer, so it carries across as many reads as it takes, and an error stops the fold. */
+
+fn fold<O,A,F,G>(&mut self, mut parser: F, init: A, mut f: G) -> io::Result<A>
+where F: FnMut(&str) -> IResult<&str,O>, G: FnMut(A,O) -> A {
+    let mut acc = init;
+    while let Some(value) = self.next(&mut parser) {
+        acc = f(acc, value?);
+    }
+    Ok(acc)
+}
+
+fn number_item(input: &str) -> IResult<&str,f64> {
+    preceded(multispace0, float64).parse(input)
+}
+
+let mut numbers = Stream::new(io::BufReader::with_capacity(3, "1 2 3\n 10.5 100".as_bytes()));
+println!("{:?}", numbers.fold(number_item, 0.0, |acc, v| acc + v));
+// Ok(116.5)
+
+/* With a real file, we use a 'BufReader' with its default capacity of 8K. However big the file is, the buffer never holds more than one chunk plus the item being parsed. */
+
+fn sum_y(filename: &str) -> io::Result<f64> {
+    let file = File::open(filename)?;
+    let mut points = Stream::new(io::BufReader::new(file));
+    points.fold(point_item, 0.0, |acc, p| acc + p.y)
+}
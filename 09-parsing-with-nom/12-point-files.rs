@@ -0,0 +1,300 @@
+/* 'pointf' parses a single "x,y" pair. Real files of points have more to them than that. There may be a header line naming the columns, comments, blank lines to separate groups of points, and a separator which isn't always a comma; tabs and semicolons are common, particularly in files that have passed through a spreadsheet. And some of them are 3D. Let's write a reader for such files which can also write the points back out again, so that a program can read a file, do something to the points, and save them in the same format.
+
+A point may or may not have a 'z' coordinate. */
+
+use nom::{IResult, Parser, Finish};
+use nom::bytes::complete::{tag, take_while};
+use nom::branch::alt;
+use nom::character::complete::{digit1, char, space0, space1, alpha1};
+use nom::combinator::{opt, recognize, map_res, value, all_consuming, cut};
+use nom::multi::separated_list1;
+use nom::sequence::{pair, delimited};
+use std::str::FromStr;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+
+// 'float64' from the numbers section
+fn float64(input: &str) -> IResult<&str,f64> { ... }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Point {
+    x: f64,
+    y: f64,
+    z: Option<f64>
+}
+
+impl Point {
+    fn dimension(&self) -> usize {
+        if self.z.is_some() {3} else {2}
+    }
+
+    fn is_finite(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite() && self.z.is_none_or(f64::is_finite)
+    }
+}
+
+/* The details of the format are kept together in a struct. 'Default' gives us plain comma-separated points with '#' comments, and struct update syntax makes it easy to change just one thing, e.g. 'Format{separator: '\t', ..Default::default()}'. If 'header' is true, then the first line which isn't blank or a comment must name the columns. */
+
+#[derive(Debug, Clone, Copy)]
+struct Format {
+    separator: char,
+    comment: char,
+    header: bool
+}
+
+impl Default for Format {
+    fn default() -> Format {
+        Format{separator: ',', comment: '#', header: false}
+    }
+}
+
+/* Errors should say which line is wrong. An I/O error isn't on any line in particular, so the error type is an enum, implementing 'Display', 'Error' and 'From<io::Error>' like the error types in the error handling chapter. */
+
+#[derive(Debug)]
+enum PointError {
+    Io(io::Error),
+    Line(usize, String)
+}
+
+impl fmt::Display for PointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PointError::Io(ref e) => write!(f, "{}", e),
+            PointError::Line(line, ref msg) => write!(f, "line {}: {}", line, msg)
+        }
+    }
+}
+
+impl Error for PointError {}
+
+impl From<io::Error> for PointError {
+    fn from(err: io::Error) -> Self {
+        PointError::Io(err)
+    }
+}
+
+/* A line is a list of fields separated by the separator. A separator can have spaces around it, unless it is itself whitespace, in which case any run of spaces and tabs will do. Returning a closure lets us decide that once, when the parser is made. 'separated_list1' collects the fields into a vector.
+
+This is 'pointf' made more general, rather than 'pointf' itself. 'pointf' has the ',' built into it, and always expects exactly two numbers, but here the separator is only known once the format is, and a line may have two coordinates or three. So we parse any number of fields and count them afterwards, which also lets an error say how many there were.
+
+Normally 'separated_list1' just stops when a field after a separator doesn't match, and leaves the separator unparsed. Then the error would be about the ',' in "3,x", when the real problem is the 'x'. Using 'cut' means that a bad field is reported as itself. 'all_consuming' insists on the whole line; it fails with 'ErrorKind::Eof' if there's anything left over. */
+
+fn separator<'a>(sep: char) -> impl FnMut(&'a str) -> IResult<&'a str,()> {
+    move |input| if sep.is_whitespace() {
+        value((), space1).parse(input)
+    } else {
+        value((), delimited(space0, char(sep), space0)).parse(input)
+    }
+}
+
+fn fields<'a,O,F>(sep: char, field: F) -> impl Parser<&'a str, Output = Vec<O>, Error = nom::error::Error<&'a str>>
+where F: Parser<&'a str, Output = O, Error = nom::error::Error<&'a str>> {
+    all_consuming(separated_list1(separator(sep), cut(field)))
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn complaint(e: nom::error::Error<&str>, what: &str) -> String {
+    if e.code == nom::error::ErrorKind::Eof {
+        format!("unexpected {}", found(e.input))
+    } else {
+        format!("expected {}, found {}", what, found(e.input))
+    }
+}
+
+fn found(rest: &str) -> String {
+    match rest.chars().next() {
+        None => "end of line".to_string(),
+        Some(c) if is_name_char(c) || c == '-' || c == '.' => {
+            let end = rest.find(|c: char| ! (is_name_char(c) || c == '-' || c == '.')).unwrap_or(rest.len());
+            format!("'{}'", &rest[..end])
+        },
+        Some(c) => format!("'{}'", c)
+    }
+}
+
+/* The reader is an iterator over points, so it reads one line at a time into a buffer that is reused, as with 'read_line' in the file chapter. Since a 'Point' is a small 'Copy' value that doesn't borrow from the buffer, we can implement the real 'Iterator' trait this time. The reader remembers how many coordinates the points have, so that a 3D point can't sneak into a 2D file, and the column names if there was a header. */
+
+struct PointReader<R> {
+    reader: R,
+    format: Format,
+    buf: String,
+    line: usize,
+    dimension: Option<usize>,
+    names: Option<Vec<String>>
+}
+
+impl <R: BufRead> PointReader<R> {
+    fn new(reader: R, format: Format) -> PointReader<R> {
+        PointReader{reader: reader, format: format, buf: String::new(), line: 0, dimension: None, names: None}
+    }
+
+    fn names(&self) -> Option<&[String]> {
+        self.names.as_deref()
+    }
+
+    fn parse_line(&mut self) -> Result<Option<Point>,PointError> {
+        let text = match self.buf.find(self.format.comment) {
+            Some(i) => &self.buf[..i],
+            None => &self.buf[..]
+        }.trim();
+        if text.is_empty() {
+            return Ok(None);
+        }
+        let line = self.line;
+        let err = |msg: String| PointError::Line(line, msg);
+
+        if self.format.header && self.names.is_none() {
+            let (_, names) = fields(self.format.separator, recognize(pair(alpha1, take_while(is_name_char)))).parse(text).finish()
+                .map_err(|e| err(complaint(e, "a column name")))?;
+            if names.len() != 2 && names.len() != 3 {
+                return Err(err(format!("expected 2 or 3 columns, found {}", names.len())));
+            }
+            self.dimension = Some(names.len());
+            self.names = Some(names.into_iter().map(|s| s.to_string()).collect());
+            return Ok(None);
+        }
+
+        let (_, coords) = fields(self.format.separator, float64).parse(text).finish()
+            .map_err(|e| err(complaint(e, "a number")))?;
+        let point = match coords[..] {
+            [x, y] => Point{x: x, y: y, z: None},
+            [x, y, z] => Point{x: x, y: y, z: Some(z)},
+            _ => return Err(err(format!("expected 2 or 3 coordinates, found {}", coords.len())))
+        };
+        match self.dimension {
+            Some(d) if d != point.dimension() =>
+                Err(err(format!("expected {} coordinates, found {}", d, point.dimension()))),
+            _ => {
+                self.dimension = Some(point.dimension());
+                Ok(Some(point))
+            }
+        }
+    }
+}
+
+/* A bad line doesn't stop the iterator. The caller can decide whether to give up on the first error or to report them all and carry on. */
+
+impl <R: BufRead> Iterator for PointReader<R> {
+    type Item = Result<Point,PointError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buf.clear();
+            match self.reader.read_line(&mut self.buf) {
+                Ok(0) => return None,
+                Ok(_) => self.line += 1,
+                Err(e) => return Some(Err(e.into()))
+            }
+            match self.parse_line() {
+                Ok(Some(p)) => return Some(Ok(p)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e))
+            }
+        }
+    }
+}
+
+// Collecting an iterator of 'Result' into a 'Result<Vec<_>,_>' stops at the first error
+fn read_points<R: BufRead>(reader: R, format: Format) -> Result<Vec<Point>,PointError> {
+    PointReader::new(reader, format).collect()
+}
+
+/* Writing is easier. The 'Display' implementation for 'f64' writes the shortest text which reads back as exactly the same number, so we don't lose any precision on the round trip. That's not so for infinity and NaN, which come out as 'inf' and 'NaN'; 'float64' doesn't read those, so a point with one of them is refused before anything is written. We don't keep the reader's column names; a header is always written as 'x' and 'y' (and 'z'). */
+
+fn write_points<W: Write>(out: &mut W, format: Format, points: &[Point]) -> io::Result<()> {
+    if let Some(p) = points.iter().find(|p| ! p.is_finite()) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("can't write {:?}", p)));
+    }
+    let sep = format.separator;
+    if format.header {
+        match points.first() {
+            Some(p) if p.dimension() == 3 => writeln!(out, "x{}y{}z", sep, sep)?,
+            _ => writeln!(out, "x{}y", sep)?
+        }
+    }
+    for p in points {
+        match p.z {
+            Some(z) => writeln!(out, "{}{}{}{}{}", p.x, sep, p.y, sep, z)?,
+            None => writeln!(out, "{}{}{}", p.x, sep, p.y)?
+        }
+    }
+    Ok(())
+}
+
+let text = "\
+# survey points
+x, y, z
+
+1.5, 2, -3
+20,52.2,0.1   # the tall one
+-1e3,0.25,7
+";
+let format = Format{header: true, ..Default::default()};
+let points = read_points(text.as_bytes(), format).unwrap();
+println!("{:?}", points);
+// [Point { x: 1.5, y: 2.0, z: Some(-3.0) }, Point { x: 20.0, y: 52.2, z: Some(0.1) }, Point { x: -1000.0, y: 0.25, z: Some(7.0) }]
+
+let mut out = Vec::new();
+write_points(&mut out, format, &points).unwrap();
+let written = String::from_utf8(out).unwrap();
+print!("{}", written);
+assert_eq!(read_points(written.as_bytes(), format).unwrap(), points);
+/*
+x,y,z
+1.5,2,-3
+20,52.2,0.1
+-1000,0.25,7
+*/
+
+// Tab separated; any run of spaces and tabs separates fields
+let tabs = Format{separator: '\t', ..Default::default()};
+println!("{:?}", read_points("1\t2\n\n3   4\n".as_bytes(), tabs));
+// Ok([Point { x: 1.0, y: 2.0, z: None }, Point { x: 3.0, y: 4.0, z: None }])
+
+// Numbers that aren't exact in binary survive the trip
+let awkward = [Point{x: 0.1 + 0.2, y: 1e-300, z: None}, Point{x: -0.0, y: 1.0/3.0, z: None}];
+let mut out = Vec::new();
+write_points(&mut out, tabs, &awkward).unwrap();
+assert_eq!(read_points(&out[..], tabs).unwrap(), awkward);
+
+// But infinity wouldn't, so it isn't written at all
+let mut out = Vec::new();
+println!("{:?}", write_points(&mut out, format, &[Point{x: 1.0, y: 2.0, z: Some(3.0)}, Point{x: f64::INFINITY, y: 0.0, z: Some(f64::NAN)}]));
+assert!(out.is_empty());
+// Err(Custom { kind: InvalidData, error: "can't write Point { x: inf, y: 0.0, z: Some(NaN) }" })
+
+/* Here is a file with several problems. Iterating over the reader reports each bad line and carries on with the next. */
+
+let bad = "1,2\n3,x\n4,5,6\n7,8\n1,2,3,4\n";
+for p in PointReader::new(bad.as_bytes(), Format::default()) {
+    match p {
+        Ok(p) => println!("{:?}", p),
+        Err(e) => println!("{}", e)
+    }
+}
+/*
+Point { x: 1.0, y: 2.0, z: None }
+line 2: expected a number, found 'x'
+line 3: expected 2 coordinates, found 3
+Point { x: 7.0, y: 8.0, z: None }
+line 5: expected 2 or 3 coordinates, found 4
+*/
+
+println!("{}", read_points("1,2\n\n3;4\n".as_bytes(), Format::default()).unwrap_err());
+println!("{}", read_points("1,2\n".as_bytes(), format).unwrap_err());
+println!("{}", read_points("1,\n".as_bytes(), Format::default()).unwrap_err());
+// line 3: unexpected ';'
+// line 1: expected a column name, found '1'
+// line 1: expected a number, found end of line
+
+// The column names are available once the header has been read
+let mut reader = PointReader::new("x;y\n1;2\n".as_bytes(), Format{separator: ';', header: true, ..Default::default()});
+println!("{:?} {:?}", reader.next(), reader.names());
+// Some(Ok(Point { x: 1.0, y: 2.0, z: None })) Some(["x", "y"])
+
+/* Blank lines are counted even though they are skipped, so the line numbers match what you see in an editor. For a big file you would use 'PointReader' directly on an 'io::BufReader' of the file rather than collecting everything into a vector; only one line is in memory at a time. */
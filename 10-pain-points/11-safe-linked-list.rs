@@ -0,0 +1,403 @@
+/* So let's take up that claim, and build a double-linked list in safe Rust. The problem is that every node is pointed to twice, once by the node before it and once by the node after it, and a value can only have one owner. We met the way out in rc1.rs: shared ownership with 'Rc', and 'RefCell' so that the nodes can still be changed. Just as with 'PlayerRef', a type alias keeps the signatures readable.
+
+If both links were 'Rc', then each pair of neighbours would keep each other alive forever, and the list would never be freed. So the forward links own the next node, and the back links are 'Weak' references, which don't keep anything alive. The list itself owns the first node, and also keeps a strong reference to the last one so that it can push at the back without walking the whole list. */
+
+use std::rc::{Rc, Weak};
+use std::cell::{RefCell, RefMut};
+use std::fmt;
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+
+type NodeRef<T> = Rc<RefCell<Node<T>>>;
+type Link<T> = Option<NodeRef<T>>;
+
+struct Node<T> {
+    value: T,
+    next: Link<T>,
+    prev: Option<Weak<RefCell<Node<T>>>>
+}
+
+struct DList<T> {
+    head: Link<T>,
+    tail: Link<T>,
+    len: usize
+}
+
+/* Following a link means cloning an 'Rc', which just bumps a reference count. Going backwards, 'upgrade' turns the 'Weak' into an 'Rc' again; it would give 'None' if the node had gone away, but the node before us is always alive, since it owns us.
+
+When a node comes out of the list, nobody else should be holding on to it, so 'Rc::try_unwrap' gets the 'RefCell' back and 'into_inner' gets the value. */
+
+fn new_node<T>(value: T) -> NodeRef<T> {
+    Rc::new(RefCell::new(Node{value: value, next: None, prev: None}))
+}
+
+// The node after 'node' and the node before it
+fn next<T>(node: &NodeRef<T>) -> Link<T> {
+    node.borrow().next.clone()
+}
+
+fn prev<T>(node: &NodeRef<T>) -> Link<T> {
+    node.borrow().prev.as_ref().and_then(|w| w.upgrade())
+}
+
+fn into_value<T>(node: NodeRef<T>) -> T {
+    match Rc::try_unwrap(node) {
+        Ok(cell) => cell.into_inner().value,
+        Err(_) => panic!("node is still shared")
+    }
+}
+
+/* All the ways of changing the list come down to two operations: putting a node in between two others, and taking one out. 'None' stands for the end of the list on either side, and then it's the list's 'head' or 'tail' that has to change. Each 'borrow_mut' only lasts for one statement, so we never have two borrows of the same node at once, which would panic. */
+
+impl <T> DList<T> {
+    fn new() -> DList<T> {
+        DList{head: None, tail: None, len: 0}
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Put 'node' between 'before' and 'after'; 'None' means the end of the list
+    fn link(&mut self, node: NodeRef<T>, before: Link<T>, after: Link<T>) {
+        {
+            let mut n = node.borrow_mut();
+            n.prev = before.as_ref().map(Rc::downgrade);
+            n.next = after.clone();
+        }
+        match before {
+            Some(ref b) => b.borrow_mut().next = Some(node.clone()),
+            None => self.head = Some(node.clone())
+        }
+        match after {
+            Some(ref a) => a.borrow_mut().prev = Some(Rc::downgrade(&node)),
+            None => self.tail = Some(node)
+        }
+        self.len += 1;
+    }
+
+    // Take 'node' out of the list, joining up its neighbours
+    fn unlink(&mut self, node: &NodeRef<T>) {
+        let (before, after) = (prev(node), next(node));
+        match before {
+            Some(ref b) => b.borrow_mut().next = after.clone(),
+            None => self.head = after.clone()
+        }
+        match after {
+            Some(ref a) => a.borrow_mut().prev = before.as_ref().map(Rc::downgrade),
+            None => self.tail = before
+        }
+        let mut n = node.borrow_mut();
+        n.next = None;
+        n.prev = None;
+        self.len -= 1;
+    }
+
+    fn push_front(&mut self, value: T) {
+        let head = self.head.clone();
+        self.link(new_node(value), None, head);
+    }
+
+    fn push_back(&mut self, value: T) {
+        let tail = self.tail.clone();
+        self.link(new_node(value), tail, None);
+    }
+
+    fn pop_front(&mut self) -> Option<T> {
+        let node = self.head.clone()?;
+        self.unlink(&node);
+        Some(into_value(node))
+    }
+
+    fn pop_back(&mut self) -> Option<T> {
+        let node = self.tail.clone()?;
+        self.unlink(&node);
+        Some(into_value(node))
+    }
+    ...
+}
+
+/* There is one catch with owning links. Dropping the list drops the first node, which drops the second node, and so on; each drop happens inside the one before, and a list of a million nodes will overflow the stack. So we break the chain ourselves, one node at a time. */
+
+impl <T> Drop for DList<T> {
+    fn drop(&mut self) {
+        let mut link = self.head.take();
+        while let Some(node) = link {
+            link = node.borrow_mut().next.take();
+        }
+    }
+}
+
+/* Pushing and popping at the ends is what 'VecDeque' is for, and it does it better. The reason to use a linked list is to insert and remove in the middle without moving everything else, and for that we need a cursor: something which points at a node, and can move along the list and change it.
+
+This cursor works like the one in the standard 'LinkedList'. It borrows the list mutably, so nothing else can change the list while it is in use. When 'current' is 'None', it is sitting on a "ghost" position between the end and the start; moving forward from there gets to the first node, and moving back gets to the last. Removing the current node moves the cursor to the next one. */
+
+struct Cursor<'a,T> {
+    list: &'a mut DList<T>,
+    current: Link<T>
+}
+
+impl <T> DList<T> {
+    ...
+    fn cursor_front(&mut self) -> Cursor<'_,T> {
+        let current = self.head.clone();
+        Cursor{list: self, current: current}
+    }
+
+    fn cursor_back(&mut self) -> Cursor<'_,T> {
+        let current = self.tail.clone();
+        Cursor{list: self, current: current}
+    }
+}
+
+/* The value lives inside a 'RefCell', so we cannot simply hand out '&mut T'. 'RefMut::map' turns the borrow of the whole node into a borrow of its value, which derefs to '&mut T' as usual. */
+
+impl <'a,T> Cursor<'a,T> {
+    fn current(&mut self) -> Option<RefMut<'_,T>> {
+        self.current.as_ref().map(|n| RefMut::map(n.borrow_mut(), |n| &mut n.value))
+    }
+
+    fn move_next(&mut self) {
+        self.current = match self.current {
+            Some(ref n) => next(n),
+            None => self.list.head.clone()
+        };
+    }
+
+    fn move_prev(&mut self) {
+        self.current = match self.current {
+            Some(ref n) => prev(n),
+            None => self.list.tail.clone()
+        };
+    }
+
+    fn insert_before(&mut self, value: T) {
+        let after = self.current.clone();
+        let before = match self.current {
+            Some(ref n) => prev(n),
+            None => self.list.tail.clone()
+        };
+        self.list.link(new_node(value), before, after);
+    }
+
+    fn insert_after(&mut self, value: T) {
+        let before = self.current.clone();
+        let after = match self.current {
+            Some(ref n) => next(n),
+            None => self.list.head.clone()
+        };
+        self.list.link(new_node(value), before, after);
+    }
+
+    fn remove_current(&mut self) -> Option<T> {
+        let node = self.current.take()?;
+        self.current = next(&node);
+        self.list.unlink(&node);
+        Some(into_value(node))
+    }
+}
+
+/* Now for iterators. This is where the safe version has to give something up. An iterator over a 'Vec' hands out '&T', but here every value is inside a 'RefCell', and a reference into a 'RefCell' is only good for as long as the 'Ref' guard from 'borrow' is alive. The guard belongs to 'next', so the reference can't be returned from it. So this iterator gives out clones of the values instead, which is fine for numbers and cheap for 'Rc'. (The raw-pointer version in the next file doesn't have this problem.)
+
+The iterator keeps a link at each end and counts down the number of values left, so that it stops when the two ends meet; that's all 'DoubleEndedIterator' needs. 'PhantomData' tells the compiler that the iterator borrows the list, so the list can't be changed under it. */
+
+struct Iter<'a,T> {
+    front: Link<T>,
+    back: Link<T>,
+    len: usize,
+    marker: PhantomData<&'a DList<T>>
+}
+
+impl <T> DList<T> {
+    ...
+    fn iter(&self) -> Iter<'_,T> {
+        Iter{front: self.head.clone(), back: self.tail.clone(), len: self.len, marker: PhantomData}
+    }
+}
+
+impl <'a,T: Clone> Iterator for Iter<'a,T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        let node = self.front.take()?;
+        self.front = next(&node);
+        self.len -= 1;
+        let value = node.borrow().value.clone();
+        Some(value)
+    }
+}
+
+impl <'a,T: Clone> DoubleEndedIterator for Iter<'a,T> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        let node = self.back.take()?;
+        self.back = prev(&node);
+        self.len -= 1;
+        let value = node.borrow().value.clone();
+        Some(value)
+    }
+}
+
+// Consuming the list is easy, since popping already moves the values out
+struct IntoIter<T>(DList<T>);
+
+impl <T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.0.pop_front()
+    }
+}
+
+impl <T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        self.0.pop_back()
+    }
+}
+
+impl <T> IntoIterator for DList<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter(self)
+    }
+}
+
+impl <T> FromIterator<T> for DList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> DList<T> {
+        let mut list = DList::new();
+        for x in iter {
+            list.push_back(x);
+        }
+        list
+    }
+}
+
+impl <T: Clone + fmt::Debug> fmt::Debug for DList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+// Pushing and popping at both ends
+let mut list = DList::new();
+list.push_back(2);
+list.push_back(3);
+list.push_front(1);
+println!("{:?} len {}", list, list.len());
+println!("{:?}", list.iter().rev().collect::<Vec<_>>());
+// [1, 2, 3] len 3
+// [3, 2, 1]
+
+println!("{:?} {:?}", list.pop_front(), list.pop_back());
+println!("{:?}", list);
+// Some(1) Some(3)
+// [2]
+
+/* Here the cursor goes down the list, removing the even numbers, and multiplying the odd numbers by ten and putting a new value after each of them. After the last node it arrives at the ghost position, where inserting "before" means at the end. Note that the 'RefMut' from 'current' has to be gone before the cursor can do anything else; the borrow checker makes sure of that, since it borrows the cursor. */
+
+let mut list: DList<i32> = (1..=5).collect();
+{
+    let mut cursor = list.cursor_front();
+    while let Some(v) = cursor.current().map(|v| *v) {
+        if v % 2 == 0 {
+            cursor.remove_current();
+        } else {
+            *cursor.current().unwrap() *= 10;
+            cursor.insert_after(v * 10 + 1);
+            cursor.move_next();
+            cursor.move_next();
+        }
+    }
+    cursor.insert_before(99);
+    cursor.move_prev();
+    cursor.move_prev();
+    cursor.insert_after(98);
+}
+println!("{:?}", list);
+// [10, 11, 30, 31, 50, 51, 98, 99]
+
+// Both ends of the iterator, and moving past the end
+let mut it = list.iter();
+println!("{:?} {:?} {:?}", it.next(), it.next_back(), it.collect::<Vec<_>>());
+// Some(10) Some(99) [11, 30, 31, 50, 51, 98]
+{
+    let mut cursor = list.cursor_back();
+    cursor.move_next();
+    println!("{:?}", cursor.current().map(|v| *v));
+    cursor.move_next();
+    println!("{:?}", cursor.current().map(|v| *v));
+}
+// None
+// Some(10)
+
+println!("{:?}", list.into_iter().rev().collect::<Vec<_>>());
+// [99, 98, 51, 50, 31, 30, 11, 10]
+
+/* It's easy to get a linked list subtly wrong, with a back link that points at the wrong node, so here is a long run of random operations checked against 'VecDeque'. The cursor walks to a random position each time and inserts or removes there. If any 'Rc' was still shared when a node came out, 'into_value' would panic, and if any 'RefCell' was borrowed twice, 'borrow_mut' would panic. */
+
+use std::collections::VecDeque;
+let mut seed: u64 = 0x2545F4914F6CDD1D;
+let mut random = move |n: u64| {
+    seed ^= seed << 13;
+    seed ^= seed >> 7;
+    seed ^= seed << 17;
+    seed % n
+};
+let mut list = DList::new();
+let mut model = VecDeque::new();
+for i in 0..20000 {
+    match random(6) {
+        0 => {
+            list.push_front(i);
+            model.push_front(i);
+        },
+        1 => {
+            list.push_back(i);
+            model.push_back(i);
+        },
+        2 => assert_eq!(list.pop_front(), model.pop_front()),
+        3 => assert_eq!(list.pop_back(), model.pop_back()),
+        _ => {
+            let pos = random(model.len() as u64 + 1) as usize;
+            let mut cursor = list.cursor_front();
+            for _ in 0..pos {
+                cursor.move_next();
+            }
+            match random(3) {
+                0 => {
+                    cursor.insert_before(i);
+                    model.insert(pos, i);
+                },
+                1 => {
+                    // after the ghost position is the front of the list
+                    cursor.insert_after(i);
+                    model.insert(if pos == model.len() {0} else {pos + 1}, i);
+                },
+                _ => {
+                    let removed = if pos < model.len() {model.remove(pos)} else {None};
+                    assert_eq!(cursor.remove_current(), removed);
+                }
+            }
+        }
+    }
+    assert_eq!(list.len(), model.len());
+}
+assert!(list.iter().eq(model.iter().cloned()));
+assert!(list.iter().rev().eq(model.iter().rev().cloned()));
+
+// And a long list can be dropped without overflowing the stack
+let big: DList<u32> = (0..1_000_000).collect();
+drop(big);
+
+/* So it can be done, and it is all safe: the worst a bug can do here is panic. But the price is clear. Every node carries two reference counts and a borrow flag, every step along the list adjusts a count, and we can't even hand out a plain reference to a value. That is why the standard library uses pointers. */
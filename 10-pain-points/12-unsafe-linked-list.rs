@@ -0,0 +1,412 @@
+/* Now the same list the way the standard library does it, with raw pointers. The API is the same as the safe version, so the two can be compared directly.
+
+A raw pointer '*mut Node<T>' is just an address, like a pointer in C. It may be null, it doesn't own anything, and the compiler makes no promises about what it points to; reading or writing through one is only allowed in an 'unsafe' block. So now the job of the borrow checker is ours. The rules we have to keep are:
+
+- every node is allocated with 'Box', and freed exactly once, when it leaves the list
+- the 'next' and 'prev' pointers of neighbouring nodes always agree, and 'head' and 'tail' are the ends
+- we never hand out a reference to a node which could outlive the node, or overlap with a '&mut' to the same value
+
+If those hold, then the outside of the list is safe to use, however badly the caller behaves. The list doesn't contain any 'Box', so 'PhantomData<Box<Node<T>>>' tells the compiler that it owns values of type 'T' all the same; that matters for the drop checker. */
+
+use std::ptr;
+use std::fmt;
+use std::marker::PhantomData;
+use std::iter::FromIterator;
+
+struct Node<T> {
+    value: T,
+    next: *mut Node<T>,
+    prev: *mut Node<T>
+}
+
+struct DList<T> {
+    head: *mut Node<T>,
+    tail: *mut Node<T>,
+    len: usize,
+    marker: PhantomData<Box<Node<T>>>
+}
+
+/* 'link' and 'unlink' are the same two operations as before, with null standing for the end of the list. They are marked 'unsafe fn' because they can only be called with pointers to nodes that are really in this list; that is a promise the caller makes, and the compiler can't check it. 'Box::into_raw' gives up ownership of a new node and hands us its address, and 'Box::from_raw' takes ownership back, so that the node is freed when the box is dropped, once the value has been moved out of it.
+
+Compare this with the safe version. There are no reference counts and no borrow flags; a link is just an address, and following it is just reading memory. */
+
+impl <T> DList<T> {
+    fn new() -> DList<T> {
+        DList{head: ptr::null_mut(), tail: ptr::null_mut(), len: 0, marker: PhantomData}
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Put a new node between 'before' and 'after'; null means the end of the list
+    unsafe fn link(&mut self, value: T, before: *mut Node<T>, after: *mut Node<T>) {
+        let node = Box::into_raw(Box::new(Node{value: value, next: after, prev: before}));
+        unsafe {
+            if before.is_null() {
+                self.head = node;
+            } else {
+                (*before).next = node;
+            }
+            if after.is_null() {
+                self.tail = node;
+            } else {
+                (*after).prev = node;
+            }
+        }
+        self.len += 1;
+    }
+
+    // Take 'node' out of the list and free it, returning its value
+    unsafe fn unlink(&mut self, node: *mut Node<T>) -> T {
+        unsafe {
+            let node = Box::from_raw(node);
+            if node.prev.is_null() {
+                self.head = node.next;
+            } else {
+                (*node.prev).next = node.next;
+            }
+            if node.next.is_null() {
+                self.tail = node.prev;
+            } else {
+                (*node.next).prev = node.prev;
+            }
+            self.len -= 1;
+            node.value
+        }
+    }
+
+    fn push_front(&mut self, value: T) {
+        let head = self.head;
+        unsafe { self.link(value, ptr::null_mut(), head) }
+    }
+
+    fn push_back(&mut self, value: T) {
+        let tail = self.tail;
+        unsafe { self.link(value, tail, ptr::null_mut()) }
+    }
+
+    fn pop_front(&mut self) -> Option<T> {
+        if self.head.is_null() {
+            None
+        } else {
+            let head = self.head;
+            Some(unsafe { self.unlink(head) })
+        }
+    }
+
+    fn pop_back(&mut self) -> Option<T> {
+        if self.tail.is_null() {
+            None
+        } else {
+            let tail = self.tail;
+            Some(unsafe { self.unlink(tail) })
+        }
+    }
+    ...
+}
+
+/* Nothing frees the nodes for us now, so 'Drop' is not optional; without it every node would leak. Popping until the list is empty frees them one by one, and there is no recursion to worry about. */
+
+impl <T> Drop for DList<T> {
+    fn drop(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+}
+
+/* The cursor has the same ghost position as before, which is now a null pointer. Because the cursor borrows the list mutably, nobody else can remove the node it points at, so the pointer stays good for as long as the cursor lives. And 'current' can now return a plain '&mut T'. Its lifetime is tied to the borrow of the cursor, so it must be gone before the cursor moves or removes anything, which is exactly what keeps it from dangling. */
+
+struct Cursor<'a,T> {
+    list: &'a mut DList<T>,
+    current: *mut Node<T>
+}
+
+impl <T> DList<T> {
+    ...
+    fn cursor_front(&mut self) -> Cursor<'_,T> {
+        let current = self.head;
+        Cursor{list: self, current: current}
+    }
+
+    fn cursor_back(&mut self) -> Cursor<'_,T> {
+        let current = self.tail;
+        Cursor{list: self, current: current}
+    }
+}
+
+impl <'a,T> Cursor<'a,T> {
+    fn current(&mut self) -> Option<&mut T> {
+        if self.current.is_null() {
+            None
+        } else {
+            Some(unsafe { &mut (*self.current).value })
+        }
+    }
+
+    fn move_next(&mut self) {
+        self.current = if self.current.is_null() {
+            self.list.head
+        } else {
+            unsafe { (*self.current).next }
+        };
+    }
+
+    fn move_prev(&mut self) {
+        self.current = if self.current.is_null() {
+            self.list.tail
+        } else {
+            unsafe { (*self.current).prev }
+        };
+    }
+
+    fn insert_before(&mut self, value: T) {
+        let after = self.current;
+        let before = if after.is_null() {self.list.tail} else {unsafe { (*after).prev }};
+        unsafe { self.list.link(value, before, after) }
+    }
+
+    fn insert_after(&mut self, value: T) {
+        let before = self.current;
+        let after = if before.is_null() {self.list.head} else {unsafe { (*before).next }};
+        unsafe { self.list.link(value, before, after) }
+    }
+
+    fn remove_current(&mut self) -> Option<T> {
+        if self.current.is_null() {
+            return None;
+        }
+        let node = self.current;
+        unsafe {
+            self.current = (*node).next;
+            Some(self.list.unlink(node))
+        }
+    }
+}
+
+/* And the iterator can give out '&T', which the safe version could not do. The lifetime ''a' doesn't appear in any of the fields, so 'PhantomData<&'a T>' is there to say that the iterator behaves as if it held a reference to the values; that keeps the list borrowed, and so alive and unchanged, for as long as the iterator and its references are in use. The count of values left stops the two ends from passing each other, as before. */
+
+struct Iter<'a,T> {
+    front: *mut Node<T>,
+    back: *mut Node<T>,
+    len: usize,
+    marker: PhantomData<&'a T>
+}
+
+impl <T> DList<T> {
+    ...
+    fn iter(&self) -> Iter<'_,T> {
+        Iter{front: self.head, back: self.tail, len: self.len, marker: PhantomData}
+    }
+}
+
+impl <'a,T> Iterator for Iter<'a,T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.len == 0 {
+            return None;
+        }
+        unsafe {
+            let node = &*self.front;
+            self.front = node.next;
+            self.len -= 1;
+            Some(&node.value)
+        }
+    }
+}
+
+impl <'a,T> DoubleEndedIterator for Iter<'a,T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        if self.len == 0 {
+            return None;
+        }
+        unsafe {
+            let node = &*self.back;
+            self.back = node.prev;
+            self.len -= 1;
+            Some(&node.value)
+        }
+    }
+}
+
+struct IntoIter<T>(DList<T>);
+
+impl <T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.0.pop_front()
+    }
+}
+
+impl <T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        self.0.pop_back()
+    }
+}
+
+impl <T> IntoIterator for DList<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter(self)
+    }
+}
+
+// Now that we have '&T', 'for' loops over '&list' work
+impl <'a,T> IntoIterator for &'a DList<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a,T>;
+
+    fn into_iter(self) -> Iter<'a,T> {
+        self.iter()
+    }
+}
+
+impl <T> FromIterator<T> for DList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> DList<T> {
+        let mut list = DList::new();
+        for x in iter {
+            list.push_back(x);
+        }
+        list
+    }
+}
+
+impl <T: fmt::Debug> fmt::Debug for DList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+// The same examples as the safe version, with the same results
+let mut list = DList::new();
+list.push_back(2);
+list.push_back(3);
+list.push_front(1);
+println!("{:?} len {}", list, list.len());
+println!("{:?}", list.iter().rev().collect::<Vec<_>>());
+// [1, 2, 3] len 3
+// [3, 2, 1]
+
+println!("{:?} {:?}", list.pop_front(), list.pop_back());
+println!("{:?}", list);
+// Some(1) Some(3)
+// [2]
+
+let mut list: DList<i32> = (1..=5).collect();
+{
+    let mut cursor = list.cursor_front();
+    while let Some(&mut v) = cursor.current() {
+        if v % 2 == 0 {
+            cursor.remove_current();
+        } else {
+            *cursor.current().unwrap() *= 10;
+            cursor.insert_after(v * 10 + 1);
+            cursor.move_next();
+            cursor.move_next();
+        }
+    }
+    cursor.insert_before(99);
+    cursor.move_prev();
+    cursor.move_prev();
+    cursor.insert_after(98);
+}
+println!("{:?}", list);
+// [10, 11, 30, 31, 50, 51, 98, 99]
+
+let mut it = list.iter();
+println!("{:?} {:?} {:?}", it.next(), it.next_back(), it.collect::<Vec<_>>());
+// Some(10) Some(99) [11, 30, 31, 50, 51, 98]
+{
+    let mut cursor = list.cursor_back();
+    cursor.move_next();
+    println!("{:?}", cursor.current());
+    cursor.move_next();
+    println!("{:?}", cursor.current());
+}
+// None
+// Some(10)
+
+println!("{:?}", list.into_iter().rev().collect::<Vec<_>>());
+// [99, 98, 51, 50, 31, 30, 11, 10]
+
+// Strings own heap memory, so a node freed twice or not at all would be noticed
+let mut words: DList<String> = "the quick brown fox".split(' ').map(|s| s.to_string()).collect();
+{
+    let mut cursor = words.cursor_back();
+    cursor.insert_before("lazy".to_string());
+    cursor.move_prev();
+    cursor.move_prev();
+    cursor.remove_current();
+}
+for w in &words {
+    print!("{} ", w);
+}
+println!();
+// the quick lazy fox
+
+/* With safe code, a mistake gives a panic or a wrong answer. With unsafe code, a mistake can give anything at all, and often it seems to work perfectly. A pointer to a freed node may well still point at the old value, until something else gets allocated there. So testing against 'VecDeque' isn't enough by itself; we also want something to check every pointer as it is used.
+
+That is what Miri does. It is an interpreter for Rust's intermediate language, which keeps track of every allocation and every borrow, and stops with an error on use-after-free, double frees, leaks, reading uninitialized memory, and references that break the aliasing rules (for example a '&T' and a '&mut T' to the same value in use at the same time). It needs a nightly compiler. Miri is slow, so this test is shorter than the one for the safe list, and uses 'String' values so that any mistakes in freeing nodes show up as memory errors. */
+
+// $ rustup +nightly component add miri
+// $ cargo +nightly miri run
+
+use std::collections::VecDeque;
+let mut seed: u64 = 0x2545F4914F6CDD1D;
+let mut random = move |n: u64| {
+    seed ^= seed << 13;
+    seed ^= seed >> 7;
+    seed ^= seed << 17;
+    seed % n
+};
+let mut list = DList::new();
+let mut model = VecDeque::new();
+for i in 0..2000 {
+    let s = i.to_string();
+    match random(6) {
+        0 => {
+            list.push_front(s.clone());
+            model.push_front(s);
+        },
+        1 => {
+            list.push_back(s.clone());
+            model.push_back(s);
+        },
+        2 => assert_eq!(list.pop_front(), model.pop_front()),
+        3 => assert_eq!(list.pop_back(), model.pop_back()),
+        _ => {
+            let pos = random(model.len() as u64 + 1) as usize;
+            let mut cursor = list.cursor_front();
+            for _ in 0..pos {
+                cursor.move_next();
+            }
+            match random(3) {
+                0 => {
+                    cursor.insert_before(s.clone());
+                    model.insert(pos, s);
+                },
+                1 => {
+                    // after the ghost position is the front of the list
+                    cursor.insert_after(s.clone());
+                    model.insert(if pos == model.len() {0} else {pos + 1}, s);
+                },
+                _ => {
+                    let removed = if pos < model.len() {model.remove(pos)} else {None};
+                    assert_eq!(cursor.remove_current(), removed);
+                }
+            }
+        }
+    }
+    assert_eq!(list.len(), model.len());
+}
+assert!(list.iter().eq(model.iter()));
+assert!(list.iter().rev().eq(model.iter().rev()));
+
+/* Only a handful of functions here use 'unsafe', but each of them depends on the rules at the top, and so does everything that calls them. That's the trade: the safe list is checked by the compiler, and this one is checked by us, with help from Miri. It is also how 'std::collections::LinkedList' is written. */